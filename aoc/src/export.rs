@@ -0,0 +1,148 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+pub type Rgb = [u8; 3];
+
+// the colour for each pixel value, value 0 uses the first colour and so on
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colours: Vec<Rgb>,
+}
+
+impl Palette {
+    pub fn new(colours: Vec<Rgb>) -> Self {
+        Palette { colours }
+    }
+
+    // black, white, and grey for anything left transparent
+    pub fn standard() -> Self {
+        Palette::new(vec![[0x00, 0x00, 0x00], [0xff, 0xff, 0xff], [0x80, 0x80, 0x80]])
+    }
+
+    // parses a comma separated list of hex colours, like "000000,ffffff"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut colours = Vec::new();
+        for hex in spec.split(',').map(|h| h.trim().trim_start_matches('#')) {
+            let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)
+                .ok_or(format!("Invalid colour '{}', expected 6 hex digits", hex))?;
+            colours.push([(value >> 16) as u8, (value >> 8) as u8, value as u8]);
+        }
+        Ok(Palette::new(colours))
+    }
+
+    pub fn colour(&self, value: usize) -> Result<Rgb, String> {
+        self.colours.get(value).copied().ok_or(format!("No colour in the palette for pixel value {}", value))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Ppm,
+    Png,
+    Svg,
+}
+
+impl Format {
+    // picks the format from a file's extension
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        match Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+            Some("ppm") => Ok(Format::Ppm),
+            Some("png") => Ok(Format::Png),
+            Some("svg") => Ok(Format::Svg),
+            _ => Err(format!("Can't tell the image format of '{}', use .ppm, .png or .svg", path)),
+        }
+    }
+}
+
+pub struct ExportOptions {
+    // the size of each pixel in the output
+    pub scale: usize,
+    pub palette: Palette,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions { scale: 10, palette: Palette::standard() }
+    }
+}
+
+fn dimensions(grid: &[Vec<usize>]) -> (usize, usize) {
+    (grid.iter().map(|r| r.len()).max().unwrap_or(0), grid.len())
+}
+
+// scaled up RGB pixels, a row at a time, short rows are padded with pixel value 0
+fn rasterize(grid: &[Vec<usize>], options: &ExportOptions) -> Result<Vec<u8>, String> {
+    let (width, _) = dimensions(grid);
+    let scale = options.scale.max(1);
+    let mut data = Vec::new();
+    for row in grid.iter() {
+        let mut line = Vec::new();
+        for x in 0..width {
+            let colour = options.palette.colour(*row.get(x).unwrap_or(&0))?;
+            for _ in 0..scale {
+                line.extend_from_slice(&colour);
+            }
+        }
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+    Ok(data)
+}
+
+pub fn to_ppm(grid: &[Vec<usize>], options: &ExportOptions) -> Result<Vec<u8>, String> {
+    let (width, height) = dimensions(grid);
+    let scale = options.scale.max(1);
+    let mut out = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+    out.extend(rasterize(grid, options)?);
+    Ok(out)
+}
+
+pub fn to_png(grid: &[Vec<usize>], options: &ExportOptions) -> Result<Vec<u8>, String> {
+    let (width, height) = dimensions(grid);
+    let scale = options.scale.max(1);
+    let data = rasterize(grid, options)?;
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+// one rect per run of same coloured pixels in a row
+pub fn to_svg(grid: &[Vec<usize>], options: &ExportOptions) -> Result<String, String> {
+    let (width, height) = dimensions(grid);
+    let scale = options.scale.max(1);
+    let mut out = String::new();
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" shape-rendering=\"crispEdges\">",
+        width * scale, height * scale).unwrap();
+    for (y, row) in grid.iter().enumerate() {
+        let mut x = 0;
+        while x < width {
+            let value = *row.get(x).unwrap_or(&0);
+            let start = x;
+            while x < width && *row.get(x).unwrap_or(&0) == value {
+                x += 1;
+            }
+            let [r, g, b] = options.palette.colour(value)?;
+            writeln!(out, "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\"/>",
+                start * scale, y * scale, (x - start) * scale, scale, r, g, b).unwrap();
+        }
+    }
+    writeln!(out, "</svg>").unwrap();
+    Ok(out)
+}
+
+// writes the grid to path in the format its extension names
+pub fn export(path: &str, grid: &[Vec<usize>], options: &ExportOptions) -> Result<(), String> {
+    let data = match Format::from_path(path)? {
+        Format::Ppm => to_ppm(grid, options)?,
+        Format::Png => to_png(grid, options)?,
+        Format::Svg => to_svg(grid, options)?.into_bytes(),
+    };
+    fs::write(path, data).map_err(|e| format!("Unable to write {}: {}", path, e))
+}
@@ -0,0 +1,124 @@
+use std::ops::Add;
+use std::ops::Mul;
+use std::ops::Sub;
+
+// Screen coordinates, x grows to the right and y grows downwards, so Up is towards smaller y.
+// Puzzles that measure distances don't care which way y goes.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    // U, D, L and R, the way wire paths and the like are written
+    pub fn from_letter(c: char) -> Option<Self> {
+        match c.to_ascii_uppercase() {
+            'U' => Some(Direction::Up),
+            'D' => Some(Direction::Down),
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Self {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub fn reverse(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    // a single step this way
+    pub fn delta(self) -> Point {
+        match self {
+            Direction::Up => Point::new(0, -1),
+            Direction::Down => Point::new(0, 1),
+            Direction::Left => Point::new(-1, 0),
+            Direction::Right => Point::new(1, 0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Self {
+        Point { x, y }
+    }
+
+    pub fn origin() -> Self {
+        Point::new(0, 0)
+    }
+
+    pub fn step(self, dir: Direction) -> Self {
+        self + dir.delta()
+    }
+
+    pub fn move_n(self, dir: Direction, n: i64) -> Self {
+        self + dir.delta() * n
+    }
+
+    pub fn manhattan(self, other: Point) -> i64 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<i64> for Point {
+    type Output = Point;
+
+    fn mul(self, n: i64) -> Point {
+        Point::new(self.x * n, self.y * n)
+    }
+}
+
+// parses a step like "R75" into its direction and distance
+pub fn parse_move(text: &str) -> Result<(Direction, i64), String> {
+    let mut chars = text.trim().chars();
+    let dir = chars.next().and_then(Direction::from_letter).ok_or(format!("Bad move '{}', expected U, D, L or R first", text))?;
+    let n = chars.as_str().parse().map_err(|_| format!("Bad move '{}', expected a distance after the direction", text))?;
+    Ok((dir, n))
+}
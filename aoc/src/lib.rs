@@ -0,0 +1,4 @@
+// code shared between the days
+pub mod export;
+pub mod grid;
+pub mod ocr;
@@ -0,0 +1,358 @@
+use std::fmt;
+
+// the block letters Advent of Code draws its answers in, 4 pixels wide (Y is 5) and 6 tall
+const STANDARD: &str = "
+A
+.##.
+#..#
+#..#
+####
+#..#
+#..#
+
+B
+###.
+#..#
+###.
+#..#
+#..#
+###.
+
+C
+.##.
+#..#
+#...
+#...
+#..#
+.##.
+
+E
+####
+#...
+###.
+#...
+#...
+####
+
+F
+####
+#...
+###.
+#...
+#...
+#...
+
+G
+.##.
+#..#
+#...
+#.##
+#..#
+.###
+
+H
+#..#
+#..#
+####
+#..#
+#..#
+#..#
+
+I
+.###
+..#.
+..#.
+..#.
+..#.
+.###
+
+J
+..##
+...#
+...#
+...#
+#..#
+.##.
+
+K
+#..#
+#.#.
+##..
+#.#.
+#.#.
+#..#
+
+L
+#...
+#...
+#...
+#...
+#...
+####
+
+O
+.##.
+#..#
+#..#
+#..#
+#..#
+.##.
+
+P
+###.
+#..#
+#..#
+###.
+#...
+#...
+
+R
+###.
+#..#
+#..#
+###.
+#.#.
+#..#
+
+S
+.###
+#...
+#...
+.##.
+...#
+###.
+
+U
+#..#
+#..#
+#..#
+#..#
+#..#
+.##.
+
+Y
+#...#
+#...#
+.#.#.
+..#..
+..#..
+..#..
+
+Z
+####
+...#
+..#.
+.#..
+#...
+####
+";
+
+#[derive(Debug)]
+pub enum OcrError {
+    // the lit pixels don't span the font's height
+    WrongHeight { expected: usize, found: usize },
+    // a letter the font doesn't have, drawn the way the font table is written
+    UnknownGlyph { column: usize, glyph: String },
+    NoText,
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::WrongHeight { expected, found } => write!(f, "text is {} pixels tall, the font is {}", found, expected),
+            OcrError::UnknownGlyph { column, glyph } => write!(f, "unknown letter at column {}:\n{}", column, glyph),
+            OcrError::NoText => write!(f, "no pixels are lit"),
+        }
+    }
+}
+
+// a glyph with its empty columns trimmed, as rows of lit pixels
+type Glyph = Vec<Vec<bool>>;
+
+fn trim_columns(rows: &[Vec<bool>]) -> Glyph {
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let lit = |x: usize| rows.iter().any(|r| r.get(x) == Some(&true));
+    let first = (0..width).find(|x| lit(*x)).unwrap_or(0);
+    let last = (0..width).rev().find(|x| lit(*x)).map_or(0, |x| x + 1);
+    rows.iter().map(|r| (first..last).map(|x| r.get(x) == Some(&true)).collect()).collect()
+}
+
+fn draw(glyph: &[Vec<bool>]) -> String {
+    glyph.iter()
+        .map(|row| row.iter().map(|px| if *px { '#' } else { '.' }).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub struct Font {
+    height: usize,
+    glyphs: Vec<(char, Glyph)>,
+}
+
+impl Font {
+    pub fn standard() -> Self {
+        Font::parse(STANDARD).unwrap()
+    }
+
+    // A font table is a list of letters separated by blank lines, each one a line holding the
+    // character followed by a line per row of pixels, where '#' is lit and anything else isn't.
+    // Letters are told apart by the empty columns between them, so a glyph can't have one inside.
+    pub fn parse(table: &str) -> Result<Self, String> {
+        let mut glyphs = Vec::new();
+        let mut height = None;
+        let lines: Vec<&str> = table.lines().map(|l| l.trim_end()).collect();
+        for block in lines.split(|l| l.is_empty()).filter(|b| !b.is_empty()) {
+            let mut name = block[0].chars();
+            let c = match (name.next(), name.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("'{}' should be a single character naming a letter", block[0])),
+            };
+            let rows: Vec<Vec<bool>> = block[1..].iter().map(|r| r.chars().map(|px| px == '#').collect()).collect();
+            if *height.get_or_insert(rows.len()) != rows.len() {
+                return Err(format!("letter {} is {} rows tall, expected {}", c, rows.len(), height.unwrap()));
+            }
+            let glyph = trim_columns(&rows);
+            if glyph.iter().all(|r| r.is_empty()) {
+                return Err(format!("letter {} has no lit pixels", c));
+            }
+            glyphs.push((c, glyph));
+        }
+        match height {
+            Some(height) => Ok(Font { height, glyphs }),
+            None => Err("font table has no letters".to_string()),
+        }
+    }
+
+    // reads the text in a grid of pixels, where true is lit
+    pub fn read(&self, grid: &[Vec<bool>]) -> Result<String, OcrError> {
+        // only the rows with something lit, so it doesn't matter how the grid was cropped
+        let first = grid.iter().position(|r| r.iter().any(|px| *px)).ok_or(OcrError::NoText)?;
+        let last = grid.iter().rposition(|r| r.iter().any(|px| *px)).unwrap();
+        let rows = &grid[first..=last];
+        if rows.len() != self.height {
+            return Err(OcrError::WrongHeight { expected: self.height, found: rows.len() });
+        }
+
+        let width = rows.iter().map(|r| r.len()).max().unwrap();
+        let lit = |x: usize| rows.iter().any(|r| r.get(x) == Some(&true));
+        let mut text = String::new();
+        let mut x = 0;
+        while x < width {
+            if !lit(x) {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < width && lit(x) {
+                x += 1;
+            }
+            let glyph: Glyph = rows.iter().map(|r| (start..x).map(|i| r.get(i) == Some(&true)).collect()).collect();
+            match self.glyphs.iter().find(|(_, g)| *g == glyph) {
+                Some((c, _)) => text.push(*c),
+                None => return Err(OcrError::UnknownGlyph { column: start, glyph: draw(&glyph) }),
+            }
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(text: &str) -> Vec<Vec<bool>> {
+        text.trim().lines().map(|l| l.trim().chars().map(|px| px == '#').collect()).collect()
+    }
+
+    // the messages from day 8 and day 11
+    const DAY8: &str = "
+        ..##..##..####.###...##..
+        ...#.#..#.#....#..#.#..#.
+        ...#.#..#.###..#..#.#..#.
+        ...#.####.#....###..####.
+        #..#.#..#.#....#.#..#..#.
+        .##..#..#.#....#..#.#..#.";
+
+    const DAY11: &str = "
+        .##..####.###..#..#.####...##..##..###.
+        #..#.#....#..#.#.#.....#....#.#..#.#..#
+        #....###..#..#.##.....#.....#.#....#..#
+        #....#....###..#.#...#......#.#....###.
+        #..#.#....#....#.#..#....#..#.#..#.#.#.
+        .##..####.#....#..#.####..##...##..#..#";
+
+    #[test]
+    fn reads_known_messages() {
+        let font = Font::standard();
+        assert_eq!(font.read(&pixels(DAY8)).unwrap(), "JAFRA");
+        assert_eq!(font.read(&pixels(DAY11)).unwrap(), "CEPKZJCR");
+    }
+
+    #[test]
+    fn empty_rows_and_columns_are_ignored() {
+        // more than one empty column between letters, and an empty row above and below
+        let text = "
+            .............
+            ..##....#..#.
+            .#..#...#..#.
+            .#..#...####.
+            .####...#..#.
+            .#..#...#..#.
+            .#..#...#..#.
+            .............";
+        assert_eq!(Font::standard().read(&pixels(text)).unwrap(), "AH");
+    }
+
+    #[test]
+    fn unknown_glyph() {
+        let text = "
+            .##..#.#
+            #..#.#.#
+            #..#..#.
+            ####..#.
+            #..#.#.#
+            #..#.#.#";
+        match Font::standard().read(&pixels(text)) {
+            Err(OcrError::UnknownGlyph { column, glyph }) => {
+                assert_eq!(column, 5);
+                assert_eq!(glyph, "#.#\n#.#\n.#.\n.#.\n#.#\n#.#");
+            },
+            other => panic!("expected an unknown glyph, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wrong_height_and_no_text() {
+        let font = Font::standard();
+        assert!(matches!(font.read(&pixels("#\n#\n#")), Err(OcrError::WrongHeight { expected: 6, found: 3 })));
+        assert!(matches!(font.read(&pixels("...\n...")), Err(OcrError::NoText)));
+    }
+
+    #[test]
+    fn custom_font() {
+        let font = Font::parse("
+X
+#.#
+.#.
+#.#
+
+I
+#
+#
+#
+").unwrap();
+        assert_eq!(font.read(&pixels("#.#.#\n.#..#\n#.#.#")).unwrap(), "XI");
+    }
+
+    #[test]
+    fn parse_errors() {
+        // the second letter is a row shorter than the first
+        let err = Font::parse("A\n#\n#\n\nB\n#\n").err().unwrap();
+        assert_eq!(err, "letter B is 1 rows tall, expected 2");
+        // a letter with no line naming it
+        let err = Font::parse("A\n#\n#\n\n##\n##\n").err().unwrap();
+        assert_eq!(err, "'##' should be a single character naming a letter");
+        assert_eq!(Font::parse("A\n..\n..").err().unwrap(), "letter A has no lit pixels");
+        assert_eq!(Font::parse("\n\n").err().unwrap(), "font table has no letters");
+    }
+}
@@ -1,6 +1,9 @@
 use std::fs;
+use std::cmp::Ordering;
+use std::env;
 use std::collections::BTreeMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::hash::Hash;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
@@ -19,8 +22,11 @@ struct Grid {
 impl Grid {
     fn new(file_path: &str) -> Self {
         // read input from file
-        let lines: Vec<String> = fs::read_to_string(file_path)
-                                .unwrap()
+        Grid::parse(&fs::read_to_string(file_path).unwrap())
+    }
+
+    fn parse(text: &str) -> Self {
+        let lines: Vec<String> = text
                                 .trim()
                                 .lines()
                                 .map(|s| s.to_string())
@@ -47,34 +53,76 @@ impl Grid {
     }
 }
 
-fn angle(src: &Point, dest: &Point) -> f32 {
-    let x = (dest.x - src.x) as f32;
-    let y = (dest.y - src.y) as f32;
-    let mut res = y.atan2(x) * 180.0 / std::f32::consts::PI;
-    res -= 270.0; // needed to set up to 0 degrees
-    while res < 0.0 { // ensure angles between 0 and 360 degrees
-        res += 360.0;
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+// The direction from one asteroid to another, reduced so every asteroid on the same line of sight
+// has the same key. Comparing keys is exact, unlike comparing angles as floats.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+struct Direction {
+    dx: i32,
+    dy: i32,
+}
+
+impl Direction {
+    const UP: Direction = Direction { dx: 0, dy: -1 };
+
+    // None for 0,0, which isn't a direction
+    fn new(dx: i32, dy: i32) -> Option<Self> {
+        let d = gcd(dx, dy);
+        if d == 0 {
+            return None;
+        }
+        Some(Direction { dx: dx / d, dy: dy / d })
+    }
+
+    fn between(src: &Point, dest: &Point) -> Self {
+        Direction::new(dest.x - src.x, dest.y - src.y).expect("no direction from an asteroid to itself")
+    }
+
+    // 0 for straight up and everything clockwise of it up to but not including straight down, 1 for the rest
+    fn half(&self) -> i32 {
+        if self.dx > 0 || (self.dx == 0 && self.dy < 0) { 0 } else { 1 }
     }
-    return res;
 }
 
-fn dist(src: &Point, dest: &Point) -> f32 {
-    let dx = (dest.x - src.x) as f32;
-    let dy = (dest.y - src.y) as f32;
-    return (dx * dx + dy * dy).sqrt()
+// Orders directions clockwise starting from straight up, the way the laser turns.
+// y grows downwards, so a is before b in the same half when b is clockwise of a, a positive cross product.
+fn compare_angle(a: &Direction, b: &Direction) -> Ordering {
+    a.half().cmp(&b.half()).then_with(|| {
+        let cross = a.dx as i64 * b.dy as i64 - a.dy as i64 * b.dx as i64;
+        0.cmp(&cross)
+    })
+}
+
+impl Ord for Direction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_angle(self, other)
+    }
+}
+
+impl PartialOrd for Direction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// squared, asteroids on the same line only need ordering
+fn dist(src: &Point, dest: &Point) -> i64 {
+    let dx = (dest.x - src.x) as i64;
+    let dy = (dest.y - src.y) as i64;
+    dx * dx + dy * dy
 }
 
 fn best_station_loc(grid: &Grid) -> (Point, i32) {
     let mut best_count = 0;
     let mut best_point = Point { x: 0, y: 0 };
     for src in grid.asteroids.iter() {
-        let mut set: HashSet<i32> = HashSet::new();
+        let mut set: HashSet<Direction> = HashSet::new();
         for dest in grid.asteroids.iter() {
-            if src != dest { 
-                // we cant use f32 as a key but need the precision
-                // so include first two dec places in int
-                let a = (angle(src, dest) * 100.0) as i32;
-                set.insert(a);
+            if src != dest {
+                set.insert(Direction::between(src, dest));
             }
         }
         if set.len() > best_count {
@@ -85,43 +133,248 @@ fn best_station_loc(grid: &Grid) -> (Point, i32) {
     return (best_point, best_count as i32);
 }
 
-fn laser_order(grid: &Grid, src: &Point) -> Vec<Point> {
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Rotation {
+    Clockwise,
+    Counterclockwise,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Vaporized {
+    point: Point,
+    // 1 for the first time round
+    rotation: usize,
+}
+
+// Vaporizes asteroids one at a time, the closest on each line of sight as the laser passes it.
+struct Laser {
+    // lines of sight in the order the laser reaches them, closest asteroid first
+    lines: Vec<VecDeque<Point>>,
+    next_line: usize,
+    rotation: usize,
+    remaining: usize,
+}
+
+impl Iterator for Laser {
+    type Item = Vaporized;
+
+    fn next(&mut self) -> Option<Vaporized> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // something is left, so this finds it within a rotation
+        loop {
+            if self.next_line == self.lines.len() {
+                self.next_line = 0;
+                self.rotation += 1;
+            }
+            self.next_line += 1;
+            if let Some(point) = self.lines[self.next_line - 1].pop_front() {
+                self.remaining -= 1;
+                return Some(Vaporized { point, rotation: self.rotation });
+            }
+        }
+    }
+}
+
+// the laser at src, first pointing at start and turning whichever way rotation says
+fn vaporize(grid: &Grid, src: &Point, start: Direction, rotation: Rotation) -> Laser {
     // BTreeMap will order our lines by angle
-    let mut angles: BTreeMap<i32, Vec<Point>> = BTreeMap::new();
+    let mut angles: BTreeMap<Direction, Vec<Point>> = BTreeMap::new();
     for dest in grid.asteroids.iter() {
         if dest != src {
-            // we cant use f32 as a key but need the precision
-            // so include first two dec places in int
-            let a = (angle(src, dest) * 100.0) as i32;
-            angles.entry(a).or_insert(Vec::new()).push(*dest);
+            angles.entry(Direction::between(src, dest)).or_insert(Vec::new()).push(*dest);
         }
     }
     // sort every vec in the BTreeMap by distance to the src
     for (_angle, dests) in angles.iter_mut() {
-        dests.sort_unstable_by_key(|dest| (dist(src, &dest) * 100.0) as i32);
+        dests.sort_unstable_by_key(|dest| dist(src, &dest));
     }
 
-    // pop the closest asteroid on each line
-    let mut order: Vec<Point> = Vec::new();
-    for _ in 0..grid.asteroids.len() - 1 {
-        for (_angle, line) in angles.iter_mut() {
-            if line.len() > 0 {
-                order.push(line.remove(0));
-            }
-        }
+    let mut lines: Vec<(Direction, Vec<Point>)> = angles.into_iter().collect();
+    if rotation == Rotation::Counterclockwise {
+        lines.reverse();
     }
+    // begin with the first line at or past start, going round from up the same way the laser turns
+    let first = lines.iter().position(|(dir, _)| match rotation {
+        Rotation::Clockwise => *dir >= start,
+        Rotation::Counterclockwise => *dir <= start,
+    });
+    lines.rotate_left(first.unwrap_or(0));
 
-    return order;
+    let remaining = lines.iter().map(|(_, dests)| dests.len()).sum();
+    let lines = lines.into_iter().map(|(_, dests)| dests.into_iter().collect()).collect();
+    Laser { lines, next_line: 0, rotation: 1, remaining }
 }
 
+// [--nth n] [--start dx,dy] [--counterclockwise] [--list]
+// --nth picks the asteroid part 2 asks for, 200 by default, --start points the laser somewhere other than up,
+// --list prints every asteroid in the order it is vaporized
 fn main() {
+    let mut nth: usize = 200;
+    let mut start = Direction::UP;
+    let mut rotation = Rotation::Clockwise;
+    let mut list = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--nth" => nth = args.next().and_then(|n| n.parse().ok()).filter(|n| *n > 0).expect("--nth needs a number from 1"),
+            "--start" => {
+                let spec = args.next().expect("--start needs a direction like 0,-1");
+                let parts: Vec<i32> = spec.split(',').map(|n| n.trim().parse().expect("--start needs a direction like 0,-1")).collect();
+                start = match parts.as_slice() {
+                    [dx, dy] => Direction::new(*dx, *dy).expect("--start can't be 0,0"),
+                    _ => panic!("--start needs a direction like 0,-1"),
+                };
+            },
+            "--counterclockwise" => rotation = Rotation::Counterclockwise,
+            "--list" => list = true,
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
     let grid = Grid::new("input.txt");
 
     let (best_loc, los_count) = best_station_loc(&grid);
     println!("Best LOS Count: {} at ({},{})", los_count, best_loc.x, best_loc.y);
 
-    let order = laser_order(&grid, &best_loc);
+    if list {
+        for (i, v) in vaporize(&grid, &best_loc, start, rotation).enumerate() {
+            println!("{}: ({},{}) on rotation {}", i + 1, v.point.x, v.point.y, v.rotation);
+        }
+    }
+
+    match vaporize(&grid, &best_loc, start, rotation).nth(nth - 1) {
+        Some(v) => {
+            println!("Asteroid {} to be vaporized is at ({},{}), on rotation {}.", nth, v.point.x, v.point.y, v.rotation);
+            println!("Part 2 answer: {}", v.point.x * 100 + v.point.y);
+        },
+        None => println!("Only {} asteroids can be vaporized, there is no asteroid {}.", grid.asteroids.len().saturating_sub(1), nth),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LARGE: &str = ".#..##.###...#######
+##.############..##.
+.#.######.########.#
+.###.#######.####.#.
+#####.##.#.##.###.##
+..#####..#.#########
+####################
+#.####....###.#.#.##
+##.#################
+#####.##.###..####..
+..######..##.#######
+####.##.####...##..#
+.#####..#.######.###
+##...#.##########...
+#.##########.#######
+.####.#.###.###.#.##
+....##.##.###..#####
+.#.#.###########.###
+#.#.#.#####.####.###
+###.##.####.##.#..##";
+
+    fn best(text: &str) -> (Point, i32) {
+        best_station_loc(&Grid::parse(text))
+    }
 
-    println!("The 200th asteroid to be vaporized is at {:?}.", order[199]);
-    println!("Part 2 answer: {}", order[199].x * 100 + order[199].y);
+    #[test]
+    fn best_station_examples() {
+        assert_eq!(best(".#..#\n.....\n#####\n....#\n...##"), (Point { x: 3, y: 4 }, 8));
+        assert_eq!(best("......#.#.\n#..#.#....\n..#######.\n.#.#.###..\n.#..#.....\n..#....#.#\n#..#....#.\n.##.#..###\n##...#..#.\n.#....####"),
+            (Point { x: 5, y: 8 }, 33));
+        assert_eq!(best("#.#...#.#.\n.###....#.\n.#....#...\n##.#.#.#.#\n....#.#.#.\n.##..###.#\n..#...##..\n..##....##\n......#...\n.####.###."),
+            (Point { x: 1, y: 2 }, 35));
+        assert_eq!(best(".#..#..###\n####.###.#\n....###.#.\n..###.##.#\n##.##.#.#.\n....###..#\n..#.#..#.#\n#..#.#.###\n.##...##.#\n.....#.#.."),
+            (Point { x: 6, y: 3 }, 41));
+        assert_eq!(best(LARGE), (Point { x: 11, y: 13 }, 210));
+    }
+
+    #[test]
+    fn vaporize_large_example() {
+        let grid = Grid::parse(LARGE);
+        let order: Vec<Point> = vaporize(&grid, &Point { x: 11, y: 13 }, Direction::UP, Rotation::Clockwise).map(|v| v.point).collect();
+        let expected = [(1, 11, 12), (2, 12, 1), (3, 12, 2), (10, 12, 8), (20, 16, 0), (50, 16, 9),
+            (100, 10, 16), (199, 9, 6), (200, 8, 2), (201, 10, 9), (299, 11, 1)];
+        for (n, x, y) in expected.iter() {
+            assert_eq!(order[n - 1], Point { x: *x, y: *y }, "asteroid {}", n);
+        }
+        assert_eq!(order.len(), 299);
+    }
+
+    #[test]
+    fn compare_angle_unreduced() {
+        let a = Direction { dx: 2, dy: -4 };
+        let b = Direction { dx: 1, dy: -2 };
+        assert_eq!(compare_angle(&a, &b), Ordering::Equal);
+        assert_eq!(Direction::new(2, -4), Some(b));
+        assert_eq!(Direction::new(0, 0), None);
+    }
+
+    #[test]
+    fn compare_angle_opposite() {
+        let up = Direction { dx: 0, dy: -1 };
+        let down = Direction { dx: 0, dy: 1 };
+        let right = Direction { dx: 1, dy: 0 };
+        let left = Direction { dx: -1, dy: 0 };
+        assert_eq!(compare_angle(&up, &down), Ordering::Less);
+        assert_eq!(compare_angle(&down, &up), Ordering::Greater);
+        assert_eq!(compare_angle(&right, &left), Ordering::Less);
+        assert_eq!(compare_angle(&left, &right), Ordering::Greater);
+        assert_eq!(compare_angle(&Direction { dx: 3, dy: 3 }, &Direction { dx: -3, dy: -3 }), Ordering::Less);
+    }
+
+    // one asteroid in each direction from the station at (2, 2), and a second one behind the one above it
+    const CROSS: &str = "..#..\n..#..\n#.#.#\n.....\n..#..";
+
+    fn order(start: Direction, rotation: Rotation) -> Vec<(Point, usize)> {
+        vaporize(&Grid::parse(CROSS), &Point { x: 2, y: 2 }, start, rotation).map(|v| (v.point, v.rotation)).collect()
+    }
+
+    fn points(list: &[(i32, i32, usize)]) -> Vec<(Point, usize)> {
+        list.iter().map(|(x, y, rotation)| (Point { x: *x, y: *y }, *rotation)).collect()
+    }
+
+    #[test]
+    fn vaporize_counterclockwise() {
+        assert_eq!(order(Direction::UP, Rotation::Clockwise), points(&[(2, 1, 1), (4, 2, 1), (2, 4, 1), (0, 2, 1), (2, 0, 2)]));
+        assert_eq!(order(Direction::UP, Rotation::Counterclockwise), points(&[(2, 1, 1), (0, 2, 1), (2, 4, 1), (4, 2, 1), (2, 0, 2)]));
+    }
+
+    #[test]
+    fn vaporize_other_starts() {
+        let right = Direction::new(1, 0).unwrap();
+        assert_eq!(order(right, Rotation::Clockwise), points(&[(4, 2, 1), (2, 4, 1), (0, 2, 1), (2, 1, 1), (2, 0, 2)]));
+        assert_eq!(order(right, Rotation::Counterclockwise), points(&[(4, 2, 1), (2, 1, 1), (0, 2, 1), (2, 4, 1), (2, 0, 2)]));
+        // between lines of sight, so the laser starts at the next one it turns to
+        let up_right = Direction::new(1, -1).unwrap();
+        assert_eq!(order(up_right, Rotation::Clockwise), points(&[(4, 2, 1), (2, 4, 1), (0, 2, 1), (2, 1, 1), (2, 0, 2)]));
+        assert_eq!(order(up_right, Rotation::Counterclockwise), points(&[(2, 1, 1), (0, 2, 1), (2, 4, 1), (4, 2, 1), (2, 0, 2)]));
+    }
+
+    #[test]
+    fn vaporize_rotations() {
+        let grid = Grid::parse(LARGE);
+        let order: Vec<Vaporized> = vaporize(&grid, &Point { x: 11, y: 13 }, Direction::UP, Rotation::Clockwise).collect();
+        // the puzzle's 200th is still on the first rotation
+        assert_eq!(order[199].rotation, 1);
+        // there are 12 asteroids straight up from the station, one goes each time round
+        let last = order.last().unwrap();
+        assert_eq!(last.point, Point { x: 11, y: 1 });
+        assert_eq!(last.rotation, 12);
+    }
+
+    #[test]
+    fn vaporize_past_the_end() {
+        let grid = Grid::parse(LARGE);
+        let mut laser = vaporize(&grid, &Point { x: 11, y: 13 }, Direction::UP, Rotation::Clockwise);
+        assert!(laser.nth(298).is_some());
+        assert!(laser.next().is_none());
+        assert!(vaporize(&grid, &Point { x: 11, y: 13 }, Direction::UP, Rotation::Clockwise).nth(299).is_none());
+        assert!(order(Direction::UP, Rotation::Counterclockwise).get(5).is_none());
+    }
 }
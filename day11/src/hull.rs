@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use aoc::grid::Point;
+
+use crate::BLACK;
+
+// the smallest rectangle holding a set of panels, inclusive on every side
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bounds {
+    pub min_x: i64,
+    pub min_y: i64,
+    pub max_x: i64,
+    pub max_y: i64,
+}
+
+impl Bounds {
+    pub fn at(p: Point) -> Self {
+        Bounds { min_x: p.x, min_y: p.y, max_x: p.x, max_y: p.y }
+    }
+
+    // grows the bounds to hold p
+    pub fn include(&mut self, p: Point) {
+        self.min_x = self.min_x.min(p.x);
+        self.min_y = self.min_y.min(p.y);
+        self.max_x = self.max_x.max(p.x);
+        self.max_y = self.max_y.max(p.y);
+    }
+
+    pub fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+}
+
+// The hull extends as far as the robot goes in any direction, so only painted panels are stored.
+// y grows downwards, the same way rows are printed.
+pub struct Hull {
+    panels: HashMap<Point, i64>,
+}
+
+impl Hull {
+    pub fn new() -> Self {
+        Hull { panels: HashMap::new() }
+    }
+
+    // panels that were never painted are black
+    pub fn get(&self, p: Point) -> i64 {
+        *self.panels.get(&p).unwrap_or(&BLACK)
+    }
+
+    pub fn paint(&mut self, p: Point, colour: i64) {
+        self.panels.insert(p, colour);
+    }
+
+    // panels painted at least once, whatever colour they ended up
+    pub fn painted(&self) -> usize {
+        self.panels.len()
+    }
+
+    // the bounds of the panels currently painted colour, None if there aren't any
+    pub fn bounds(&self, colour: i64) -> Option<Bounds> {
+        let mut panels = self.panels.iter().filter(|(_, c)| **c == colour).map(|(p, _)| *p);
+        let mut bounds = Bounds::at(panels.next()?);
+        for p in panels {
+            bounds.include(p);
+        }
+        Some(bounds)
+    }
+
+    // the panels within bounds as rows of colours
+    pub fn to_grid(&self, bounds: Bounds) -> Vec<Vec<i64>> {
+        (bounds.min_y..=bounds.max_y)
+            .map(|y| (bounds.min_x..=bounds.max_x).map(|x| self.get(Point::new(x, y))).collect())
+            .collect()
+    }
+}
@@ -1,8 +1,25 @@
 extern crate queues;
 
-use std::collections::HashSet;
+mod hull;
+mod memview;
+mod replay;
+
+use std::collections::BTreeSet;
+use std::env;
 use std::fs;
+use std::time::Duration;
 use queues::*;
+use aoc::export;
+use aoc::export::ExportOptions;
+use aoc::export::Palette;
+use aoc::grid::Direction;
+use aoc::grid::Point;
+use aoc::ocr::Font;
+use hull::Hull;
+use memview::Snapshot;
+use memview::ViewOptions;
+use replay::Replay;
+use replay::ReplayOptions;
 
 enum Opcode {
     ADD,
@@ -125,6 +142,8 @@ struct Computer {
     output: Queue<i64>,
     inst_pointer: usize,
     relative_base: usize,
+    // cells written since the last snapshot
+    written: BTreeSet<usize>,
 }
 
 impl Computer {
@@ -142,6 +161,14 @@ impl Computer {
             output: Queue::new(),
             inst_pointer: 0,
             relative_base: 0,
+            written: BTreeSet::new(),
+        }
+    }
+
+    fn snapshot(&mut self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            written: std::mem::take(&mut self.written),
         }
     }
 
@@ -157,6 +184,7 @@ impl Computer {
             self.memory.resize(addr + 10, 0);
         }
         self.memory[addr] = value;
+        self.written.insert(addr);
     }
 
     fn run(&mut self) -> bool {
@@ -261,57 +289,15 @@ impl Computer {
 const BLACK: i64 = 0;
 const WHITE: i64 = 1;
 
-#[derive(Copy, Clone)]
-enum Direction {
-    UP,
-    DOWN,
-    LEFT,
-    RIGHT,
-}
-
-fn calc_move(dir: Direction, x: usize, y: usize) -> (usize, usize) {
-    match dir {
-        Direction::UP => (x, y - 1),
-        Direction::DOWN => (x, y + 1),
-        Direction::LEFT => (x - 1, y),
-        Direction::RIGHT => (x + 1, y),
-    }
-}
-
+// the robot turns left for 0 and right for 1
 fn calc_turn(cur_dir: Direction, turn: i64) -> Direction {
-    match cur_dir {
-        Direction::UP => {
-            match turn {
-                0 => Direction::LEFT,
-                1 => Direction::RIGHT,
-                _ => panic!("Unknown turn"),
-            }
-        },
-        Direction::DOWN => {
-            match turn {
-                0 => Direction::RIGHT,
-                1 => Direction::LEFT,
-                _ => panic!("Unknown turn"),
-            }
-        },
-        Direction::LEFT => {
-            match turn {
-                0 => Direction::DOWN,
-                1 => Direction::UP,
-                _ => panic!("Unknown turn"),
-            }
-        },
-        Direction::RIGHT => {
-            match turn {
-                0 => Direction::UP,
-                1 => Direction::DOWN,
-                _ => panic!("Unknown turn"),
-            }
-        },
+    match turn {
+        0 => cur_dir.turn_left(),
+        1 => cur_dir.turn_right(),
+        _ => panic!("Unknown turn"),
     }
 }
 
-#[allow(dead_code)]
 fn print_grid(grid: &Vec<Vec<i64>>) {
     for row in grid.iter() {
         for cell in row.iter() {
@@ -325,35 +311,176 @@ fn print_grid(grid: &Vec<Vec<i64>>) {
     }
 }
 
-fn main() {
-    let width = 45;
-    let height = 8;
-    let mut grid: Vec<Vec<i64>> = vec![vec![BLACK; width]; height];
-    let mut rx: usize = 0;
-    let mut ry: usize = 0;
-    grid[ry][rx] = WHITE;
-    let mut dir = Direction::UP;
-    let mut set: HashSet<(usize, usize)> = HashSet::new();
+// one move of the robot, where it was, what it painted there, which way it turned
+// and the direction it faces afterwards
+#[derive(Copy, Clone)]
+struct Step {
+    pos: Point,
+    colour: i64,
+    turn: i64,
+    dir: Direction,
+}
 
-    let mut comp = Computer::new("input.txt");
+// runs the painting robot until its program halts, starting on a panel of the given colour,
+// on_step is called after every move
+fn run_robot<F: FnMut(&mut Computer, &Step)>(comp: &mut Computer, start: i64, mut on_step: F) -> Hull {
+    let mut hull = Hull::new();
+    let mut pos = Point::origin();
+    if start != BLACK {
+        hull.paint(pos, start);
+    }
+    let mut dir = Direction::Up;
     loop {
         // provide input to the robots camera
-        comp.input.add(grid[ry][rx]).unwrap();
+        comp.input.add(hull.get(pos)).unwrap();
         let should_halt = comp.run();
-        // paint the grid the new color
+        if comp.output.size() < 2 {
+            // the program halted without another move
+            break;
+        }
+        // paint the panel the new color
         let new_col = comp.output.remove().unwrap();
-        grid[ry as usize][rx as usize] = new_col;
-        set.insert((rx, ry));
+        hull.paint(pos, new_col);
         // turn and move the robot
         let new_dir = comp.output.remove().unwrap();
         dir = calc_turn(dir, new_dir);
-        let r = calc_move(dir, rx, ry);
-        rx = r.0;
-        ry = r.1;
+        on_step(comp, &Step { pos, colour: new_col, turn: new_dir, dir });
+        pos = pos.step(dir);
         if should_halt {
             break;
         }
     }
-    println!("Number of cells painted once: {}", set.len());
-    print_grid(&grid);
-}
\ No newline at end of file
+    hull
+}
+
+// when both parts run, each gets its own file
+fn part_path(path: &str, part: u32, parts: usize) -> String {
+    if parts == 1 {
+        return path.to_string();
+    }
+    match path.rfind('.') {
+        Some(i) => format!("{}-part{}{}", &path[..i], part, &path[i..]),
+        None => format!("{}-part{}", path, part),
+    }
+}
+
+// [--part 1|2] [--font table] [--export image.png|.ppm|.svg] [--scale n] [--palette 000000,ffffff] [--memview]
+// [--replay] [--gif path] [--every n] [--delay ms] [--columns n] [--no-color]
+// Part 1 starts on a black panel and counts the panels painted, part 2 starts on a white panel
+// and prints the registration identifier. Without --part both are run.
+fn main() {
+    let mut parts: Vec<u32> = vec![1, 2];
+    let mut font = Font::standard();
+    let mut export_path: Option<String> = None;
+    let mut export_options = ExportOptions::default();
+    let mut replay = false;
+    let mut gif_path: Option<String> = None;
+    let mut replay_options = ReplayOptions::default();
+    let mut memview = false;
+    let mut view = ViewOptions::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--part" => parts = vec![args.next().and_then(|p| p.parse().ok()).filter(|p| *p == 1 || *p == 2).expect("--part needs 1 or 2")],
+            "--font" => {
+                let path = args.next().expect("--font needs a path");
+                let table = fs::read_to_string(&path).expect("Unable to read font table");
+                font = Font::parse(&table).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            },
+            "--export" => export_path = Some(args.next().expect("--export needs a path")),
+            "--scale" => {
+                export_options.scale = args.next().and_then(|n| n.parse().ok()).expect("--scale needs a number");
+                replay_options.scale = export_options.scale;
+            },
+            "--palette" => {
+                let spec = args.next().expect("--palette needs a list of colours");
+                export_options.palette = Palette::parse(&spec).unwrap_or_else(|e| panic!("{}", e));
+            },
+            // play the robot's run back in the terminal, or as an animated gif
+            "--replay" => replay = true,
+            "--gif" => gif_path = Some(args.next().expect("--gif needs a path")),
+            "--every" => replay_options.every = args.next().and_then(|n| n.parse().ok()).expect("--every needs a number"),
+            "--delay" => replay_options.delay = Duration::from_millis(args.next().and_then(|n| n.parse().ok()).expect("--delay needs a number")),
+            // show the memory the program changed between each pair of outputs
+            "--memview" => memview = true,
+            "--columns" => view.columns = args.next().and_then(|c| c.parse().ok()).expect("--columns needs a number"),
+            "--no-color" => view.color = false,
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    let part_count = parts.len();
+    for part in parts {
+        let mut comp = Computer::new("input.txt");
+        let mut last = comp.snapshot();
+        let mut all_written: BTreeSet<usize> = BTreeSet::new();
+        let mut count = 0;
+        let start = if part == 1 { BLACK } else { WHITE };
+        let mut recording = Replay::new(start);
+        let hull = run_robot(&mut comp, start, |comp, step| {
+            recording.record(step);
+            if memview {
+                let snap = comp.snapshot();
+                println!("Step {} at ({}, {}): painted {}, turned {}, {} cells written",
+                    count, step.pos.x, step.pos.y, step.colour, step.turn, snap.written.len());
+                print!("{}", memview::format_diff(&last, &snap, &view));
+                all_written.extend(snap.written.iter());
+                last = snap;
+            }
+            count += 1;
+        });
+        if memview {
+            // everything the program ever wrote, not just in the last step
+            last.written = all_written;
+            println!("Final memory:");
+            print!("{}", memview::dump(&last, &view));
+        }
+
+        if replay {
+            recording.play(&replay_options);
+        }
+        if let Some(path) = gif_path.as_ref() {
+            let path = part_path(path, part, part_count);
+            recording.write_gif(&path, &replay_options).unwrap_or_else(|e| panic!("{}", e));
+            println!("Replay written to {}", path);
+        }
+
+        if part == 1 {
+            println!("Part 1: {} panels painted at least once", hull.painted());
+            continue;
+        }
+        match hull.bounds(WHITE) {
+            Some(bounds) => {
+                println!("Part 2: {}x{} registration identifier", bounds.width(), bounds.height());
+                let grid = hull.to_grid(bounds);
+                print_grid(&grid);
+                let pixels: Vec<Vec<bool>> = grid.iter().map(|row| row.iter().map(|c| *c == WHITE).collect()).collect();
+                match font.read(&pixels) {
+                    Ok(text) => println!("Registration identifier: {}", text),
+                    Err(e) => println!("Unable to read the identifier: {}", e),
+                }
+                if let Some(path) = export_path.as_ref() {
+                    let grid: Vec<Vec<usize>> = grid.iter().map(|row| row.iter().map(|c| *c as usize).collect()).collect();
+                    export::export(path, &grid, &export_options).unwrap_or_else(|e| panic!("{}", e));
+                    println!("Hull written to {}", path);
+                }
+            },
+            None => println!("Nothing was painted white"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        let hull = run_robot(&mut Computer::new("input.txt"), BLACK, |_, _| ());
+        assert_eq!(hull.painted(), 2268);
+        let hull = run_robot(&mut Computer::new("input.txt"), WHITE, |_, _| ());
+        let grid = hull.to_grid(hull.bounds(WHITE).unwrap());
+        let pixels: Vec<Vec<bool>> = grid.iter().map(|row| row.iter().map(|c| *c == WHITE).collect()).collect();
+        assert_eq!(Font::standard().read(&pixels).unwrap(), "CEPKZJCR");
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// highlighted cells are drawn in bold yellow, or with a * in front when colour is off
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+
+pub struct ViewOptions {
+    pub columns: usize,
+    pub color: bool,
+}
+
+impl Default for ViewOptions {
+    fn default() -> Self {
+        ViewOptions { columns: 8, color: true }
+    }
+}
+
+// the state of memory at some point, with every cell written since the snapshot before it
+#[derive(Clone)]
+pub struct Snapshot {
+    pub memory: Vec<i64>,
+    pub written: BTreeSet<usize>,
+}
+
+pub struct CellDiff {
+    pub addr: usize,
+    // memory grows on demand, cells past the end of the older snapshot read as 0
+    pub before: i64,
+    pub after: i64,
+}
+
+fn width_of(value: i64) -> usize {
+    value.to_string().len()
+}
+
+fn cell(out: &mut String, value: i64, width: usize, highlight: bool, options: &ViewOptions) {
+    if highlight && options.color {
+        write!(out, " {}{:>w$}{}", HIGHLIGHT, value, RESET, w = width).unwrap();
+    } else {
+        let marker = if highlight { '*' } else { ' ' };
+        write!(out, " {}{:>w$}", marker, value, w = width).unwrap();
+    }
+}
+
+// prints memory in rows of columns cells, each row starting with the address of its first cell,
+// cells written since the previous snapshot are highlighted
+pub fn dump(snapshot: &Snapshot, options: &ViewOptions) -> String {
+    let memory = &snapshot.memory;
+    let columns = options.columns.max(1);
+    let addr_width = width_of(memory.len() as i64);
+    let width = memory.iter().map(|v| width_of(*v)).max().unwrap_or(1);
+    let mut out = String::new();
+    for (row, cells) in memory.chunks(columns).enumerate() {
+        write!(out, "{:>w$}:", row * columns, w = addr_width).unwrap();
+        for (i, value) in cells.iter().enumerate() {
+            let addr = row * columns + i;
+            cell(&mut out, *value, width, snapshot.written.contains(&addr), options);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Vec<CellDiff> {
+    let len = before.memory.len().max(after.memory.len());
+    (0..len)
+        .map(|addr| CellDiff {
+            addr,
+            before: before.memory.get(addr).copied().unwrap_or(0),
+            after: after.memory.get(addr).copied().unwrap_or(0),
+        })
+        .filter(|d| d.before != d.after)
+        .collect()
+}
+
+// only the rows containing a change are shown, as the old row then the new one
+pub fn format_diff(before: &Snapshot, after: &Snapshot, options: &ViewOptions) -> String {
+    let changes = diff(before, after);
+    let columns = options.columns.max(1);
+    let rows: BTreeSet<usize> = changes.iter().map(|d| d.addr / columns).collect();
+    let changed: BTreeSet<usize> = changes.iter().map(|d| d.addr).collect();
+    let len = before.memory.len().max(after.memory.len());
+    let addr_width = width_of(len as i64);
+    let width = before.memory.iter().chain(after.memory.iter()).map(|v| width_of(*v)).max().unwrap_or(1);
+
+    let mut out = String::new();
+    for row in rows {
+        for (sign, memory) in [('-', &before.memory), ('+', &after.memory)].iter() {
+            write!(out, "{}{:>w$}:", sign, row * columns, w = addr_width).unwrap();
+            for addr in row * columns..(row * columns + columns).min(len) {
+                let value = memory.get(addr).copied().unwrap_or(0);
+                cell(&mut out, value, width, changed.contains(&addr), options);
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
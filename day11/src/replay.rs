@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use aoc::grid::Direction;
+use aoc::grid::Point;
+
+use crate::hull::Bounds;
+use crate::hull::Hull;
+use crate::Step;
+use crate::BLACK;
+use crate::WHITE;
+
+// colour indices in the gif's palette
+const GIF_PALETTE: [u8; 9] = [0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0x30, 0x30];
+const GIF_ROBOT: u8 = 2;
+
+pub struct ReplayOptions {
+    // draw a frame every this many steps
+    pub every: usize,
+    // time between frames
+    pub delay: Duration,
+    // the size of a panel in the gif
+    pub scale: usize,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions { every: 1, delay: Duration::from_millis(50), scale: 4 }
+    }
+}
+
+// everything the robot did, enough to rebuild the hull at any point in the run
+pub struct Replay {
+    start: i64,
+    steps: Vec<Step>,
+}
+
+// the state of the run after some number of steps
+struct Frame {
+    hull: Hull,
+    robot: Point,
+    dir: Direction,
+}
+
+impl Replay {
+    pub fn new(start: i64) -> Self {
+        Replay { start, steps: Vec::new() }
+    }
+
+    pub fn record(&mut self, step: &Step) {
+        self.steps.push(*step);
+    }
+
+    // the whole area the robot covers, so every frame is the same size
+    fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::at(Point::origin());
+        for step in self.steps.iter() {
+            bounds.include(step.pos);
+            bounds.include(step.pos.step(step.dir));
+        }
+        bounds
+    }
+
+    // every frame of the run, from before the first step to after the last
+    fn frames<F: FnMut(&Frame)>(&self, every: usize, mut draw: F) {
+        let mut frame = Frame { hull: Hull::new(), robot: Point::origin(), dir: Direction::Up };
+        if self.start != BLACK {
+            frame.hull.paint(Point::origin(), self.start);
+        }
+        draw(&frame);
+        for (i, step) in self.steps.iter().enumerate() {
+            frame.hull.paint(step.pos, step.colour);
+            frame.robot = step.pos.step(step.dir);
+            frame.dir = step.dir;
+            if (i + 1) % every.max(1) == 0 || i + 1 == self.steps.len() {
+                draw(&frame);
+            }
+        }
+    }
+
+    // plays the run back in the terminal, redrawing in place
+    pub fn play(&self, options: &ReplayOptions) {
+        let bounds = self.bounds();
+        let mut count = 0;
+        self.frames(options.every, |frame| {
+            let mut out = String::from("\x1b[H\x1b[2J");
+            for y in bounds.min_y..=bounds.max_y {
+                for x in bounds.min_x..=bounds.max_x {
+                    let p = Point::new(x, y);
+                    if p == frame.robot {
+                        let arrow = match frame.dir {
+                            Direction::Up => '^',
+                            Direction::Down => 'v',
+                            Direction::Left => '<',
+                            Direction::Right => '>',
+                        };
+                        out.push_str(&format!("\x1b[1;31m{}\x1b[0m", arrow));
+                    } else if frame.hull.get(p) == WHITE {
+                        out.push('#');
+                    } else {
+                        out.push('.');
+                    }
+                }
+                out.push('\n');
+            }
+            out.push_str(&format!("Step {} of {}\n", count.min(self.steps.len()), self.steps.len()));
+            print!("{}", out);
+            std::io::stdout().flush().unwrap();
+            count += options.every.max(1);
+            thread::sleep(options.delay);
+        });
+    }
+
+    // writes the run as an animated gif, with the robot drawn in red
+    pub fn write_gif(&self, path: &str, options: &ReplayOptions) -> Result<(), String> {
+        let bounds = self.bounds();
+        let scale = options.scale.max(1);
+        let (width, height) = (bounds.width() * scale, bounds.height() * scale);
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(format!("{}x{} is too big for a gif, try a smaller scale", width, height));
+        }
+        let file = File::create(path).map_err(|e| format!("Unable to create {}: {}", path, e))?;
+        let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &GIF_PALETTE).map_err(|e| e.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+        let mut result = Ok(());
+        self.frames(options.every, |frame| {
+            if result.is_err() {
+                return;
+            }
+            let mut pixels = vec![0u8; width * height];
+            for y in bounds.min_y..=bounds.max_y {
+                for x in bounds.min_x..=bounds.max_x {
+                    let p = Point::new(x, y);
+                    let index = if p == frame.robot { GIF_ROBOT } else { frame.hull.get(p) as u8 };
+                    let (px, py) = ((x - bounds.min_x) as usize * scale, (y - bounds.min_y) as usize * scale);
+                    for row in pixels[py * width..(py + scale) * width].chunks_mut(width) {
+                        for p in row[px..px + scale].iter_mut() {
+                            *p = index;
+                        }
+                    }
+                }
+            }
+            let mut gif_frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+            // gif delays are in hundredths of a second
+            gif_frame.delay = (options.delay.as_millis() / 10) as u16;
+            result = encoder.write_frame(&gif_frame).map_err(|e| e.to_string());
+        });
+        result
+    }
+}
@@ -56,13 +56,14 @@ fn print_tape(tape: &Vec<i32>) {
 }
 
 fn main() {
-    // get the length of the tape so we dont overflow our vector
-    let tape_len: i32 = load_tape("input.txt").len() as i32;
+    // load the tape once and get its length so we dont overflow our vector
+    let original: Vec<i32> = load_tape("input.txt");
+    let tape_len: i32 = original.len() as i32;
     // loop over all combinations of noun and verb
     'outer: for noun in 0..tape_len {
         for verb in 0..tape_len {
-            // load a fresh tape
-            let mut tape: Vec<i32> = load_tape("input.txt");
+            // start from a fresh copy of the tape
+            let mut tape: Vec<i32> = original.clone();
             // calculate the result with the given noun and verb
             let res = run_tape(&mut tape, noun, verb);
             // check if we should stop
@@ -1,41 +1,20 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-
-struct Point {
-    x: i32,
-    y: i32,
-}
+use aoc::grid;
+use aoc::grid::Point;
 
 struct Line {
     start: Point,
     end: Point,
 }
 
-fn build_line(inst: &str, cur_x: &mut i32, cur_y: &mut i32) -> Line {
-    let dir = &inst[0..1];
-    let mag = &inst[1..inst.len()];
-    let mag = mag.parse::<i32>().unwrap();
-    let start_x = *cur_x;
-    let start_y = *cur_y;
-    match dir {
-        "U" => *cur_y += mag,
-        "D" => *cur_y -= mag,
-        "L" => *cur_x -= mag,
-        "R" => *cur_x += mag,
-        _ => {
-            println!("Bad line: {}", inst);
-            panic!();
-        },
-    }
+fn build_line(inst: &str, cur: &mut Point) -> Line {
+    let (dir, mag) = grid::parse_move(inst).unwrap_or_else(|e| panic!("{}", e));
+    let start = *cur;
+    *cur = cur.move_n(dir, mag);
     return Line {
-        start: Point {
-            x: start_x,
-            y: start_y,
-        },
-        end: Point {
-            x: *cur_x,
-            y: *cur_y,
-        },
+        start,
+        end: *cur,
     }
 }
 
@@ -48,11 +27,11 @@ fn parse_input(file_path: &str) -> Vec<Vec<Line>> {
     for line in reader.lines() {
         let line = line.unwrap();
         let mut wire = Vec::new();
-        let mut cur_x = 0;
-        let mut cur_y = 0;
+        let mut cur = Point::origin();
         for s in line.split(",") {
-            let line = build_line(s, &mut cur_x, &mut cur_y);
-            println!("Built a line from ({}, {}) -> ({}, {})", line.start.x, line.start.y, line.end.x, line.end.y);
+            let line = build_line(s, &mut cur);
+            // aoc::grid has y growing downwards, so y is negated when printing to keep U as +y
+            println!("Built a line from ({}, {}) -> ({}, {})", line.start.x, -line.start.y, line.end.x, -line.end.y);
             wire.push(line);
         }
         wires.push(wire);
@@ -61,7 +40,7 @@ fn parse_input(file_path: &str) -> Vec<Vec<Line>> {
     return wires;
 }
 
-fn within(test: i32, range_start: i32, range_end: i32) -> bool {
+fn within(test: i64, range_start: i64, range_end: i64) -> bool {
     if range_start < range_end {
         return test >= range_start && test <= range_end;
     } else {
@@ -82,22 +61,19 @@ fn line_intersects(line0: &Line, line1: &Line) -> (bool, Point) {
     if is_horizontal(line0) && is_vertical(line1) {
         // line0's y is constant and line1's x is constant
         if within(line1.start.x, line0.start.x, line0.end.x) && within(line0.start.y, line1.start.y, line1.end.y)  {
-            println!("Intersection at ({}, {})!", line1.start.x, line0.start.y);
+            // y is negated here too, as when printing the lines
+            println!("Intersection at ({}, {})!", line1.start.x, -line0.start.y);
             return (true, Point { x: line1.start.x, y: line0.start.y });
         }
     } else if is_horizontal(line1) && is_vertical(line0) {
         // line0's x is constant and line1's y is constant
         if within(line0.start.x, line1.start.x, line1.end.x) && within(line1.start.y, line0.start.y, line0.end.y)  {
-            println!("Intersection at ({}, {})!", line0.start.x, line1.start.y);
+            println!("Intersection at ({}, {})!", line0.start.x, -line1.start.y);
             return (true, Point { x: line0.start.x, y: line1.start.y });
         }
     }
 
-    return (false, Point { x: 0, y: 0 });
-}
-
-fn manhattan_dist(p0: &Point, p1: &Point) -> i32 {
-    return (p0.x - p1.x).abs() + (p0.y - p1.y).abs();
+    return (false, Point::origin());
 }
 
 fn point_on_line(p0: &Point, line: &Line) -> bool {
@@ -109,7 +85,7 @@ fn point_on_line(p0: &Point, line: &Line) -> bool {
     return false;
 }
 
-fn line_len(line: &Line) -> i32 {
+fn line_len(line: &Line) -> i64 {
     if is_horizontal(line) { // y is constant
         return (line.start.x - line.end.x).abs();
     } else if is_vertical(line) { // x is constant
@@ -118,7 +94,7 @@ fn line_len(line: &Line) -> i32 {
     panic!(); // line must be hori or vert only
 }
 
-fn signal_delay(p0: &Point, wire: &Vec<Line>) -> i32 {
+fn signal_delay(p0: &Point, wire: &Vec<Line>) -> i64 {
     // find the line the point is on
     let mut delay_so_far = 0;
     for line in wire.iter() {
@@ -154,10 +130,10 @@ fn main() {
     }
 
     // find the closest intersection to the origin
-    let origin = Point { x: 0, y: 0 };
-    let mut shortest_dist = std::i32::MAX;
+    let origin = Point::origin();
+    let mut shortest_dist = std::i64::MAX;
     for ip in intersects.iter() {
-        let new_dist = manhattan_dist(&origin, &ip);
+        let new_dist = origin.manhattan(*ip);
         if new_dist < shortest_dist {
             shortest_dist = new_dist;
         }
@@ -165,7 +141,7 @@ fn main() {
     println!("Shortest distance is {}", shortest_dist);
 
     // find shortest dignal delay
-    let mut shortest_delay = std::i32::MAX;
+    let mut shortest_delay = std::i64::MAX;
     for ip in intersects.iter() {
         let new_delay = signal_delay(&ip, wire0) + signal_delay(&ip, wire1);
         if new_delay < shortest_delay {
@@ -31,6 +31,20 @@ impl Opcode {
             _ => panic!("Unknown opcode {}", i),
         }
     }
+
+    fn param_count(&self) -> u32 {
+        match self {
+            Opcode::ADD => 3,
+            Opcode::MUL => 3,
+            Opcode::INP => 1,
+            Opcode::OUT => 1,
+            Opcode::JIT => 2,
+            Opcode::JIF => 2,
+            Opcode::LT => 3,
+            Opcode::EQ => 3,
+            Opcode::HALT => 0,
+        }
+    }
 }
 
 enum ParamMode {
@@ -60,6 +74,14 @@ impl Parameter {
             ParamMode::IMMEDIATE => self.value,
         }
     }
+
+    // the address a parameter that is written to refers to
+    fn get_idx(&self) -> usize {
+        match self.mode {
+            ParamMode::POSITION => self.value as usize,
+            ParamMode::IMMEDIATE => panic!("Index should never be in immediate mode"),
+        }
+    }
 }
 
 struct Instruction {
@@ -73,59 +95,21 @@ impl Instruction {
     }
 
     fn new(icode: i32, memory: &Vec<i32>, index: usize) -> Self {
+        // parse the opcode from the instruction code
         let opcode = Opcode::from_i32(icode % 100);
-        match opcode {
-            Opcode::ADD | Opcode::MUL | Opcode::LT | Opcode::EQ => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: vec![
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 100) % 10),
-                            value: memory[index + 1],
-                        },
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 1000) % 10),
-                            value: memory[index + 2],
-                        },
-                        Parameter {
-                            mode: ParamMode::IMMEDIATE,
-                            value: memory[index + 3],
-                        },
-                    ],
-                }
-            },
-            Opcode::INP | Opcode::OUT => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: vec![
-                        Parameter {
-                            mode: ParamMode::IMMEDIATE,
-                            value: memory[index + 1],
-                        },
-                    ],
-                }
-            },
-            Opcode::JIT | Opcode::JIF => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: vec![
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 100) % 10),
-                            value: memory[index + 1],
-                        },
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 1000) % 10),
-                            value: memory[index + 2],
-                        },
-                    ],
-                }
-            },
-            Opcode::HALT => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: Vec::new(),
-                }
-            },
+        // get the param modes and values for each param in the instruction,
+        // including the ones that are written to
+        let param_count = opcode.param_count();
+        let mut params = Vec::new();
+        for i in 0..param_count {
+            params.push(Parameter {
+                mode: ParamMode::from_i32((icode / (100 * 10i32.pow(i))) % 10),
+                value: memory[index + 1 + i as usize],
+            });
+        }
+        Instruction {
+            opcode,
+            parameters: params,
         }
     }
 }
@@ -162,22 +146,22 @@ impl Computer {
                 Opcode::ADD => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     self.memory[idx] = val0 + val1;
                 },
                 Opcode::MUL => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     self.memory[idx] = val0 * val1;
                 },
                 Opcode::INP => {
-                    let idx = inst.parameters[0].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[0].get_idx();
                     self.memory[idx] = self.input.remove().expect("Input queue is empty");
                 },
                 Opcode::OUT => {
-                    let idx = inst.parameters[0].get_value(&self.memory) as usize;
-                    self.output.push(self.memory[idx]);
+                    let outp = inst.parameters[0].get_value(&self.memory);
+                    self.output.push(outp);
                 },
                 Opcode::JIT => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
@@ -198,7 +182,7 @@ impl Computer {
                 Opcode::LT => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     if val0 < val1 {
                         self.memory[idx] = 1;
                     } else {
@@ -208,7 +192,7 @@ impl Computer {
                 Opcode::EQ => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     if val0 == val1 {
                         self.memory[idx] = 1;
                     } else {
@@ -232,3 +216,35 @@ fn main() {
         println!("{}", o);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(memory: Vec<i32>) -> Computer {
+        let mut comp = Computer { memory, input: Queue::new(), output: Vec::new() };
+        comp.run();
+        comp
+    }
+
+    #[test]
+    fn out_immediate() {
+        assert_eq!(run(vec![104, 42, 99]).output, vec![42]);
+    }
+
+    #[test]
+    fn out_position() {
+        assert_eq!(run(vec![4, 3, 99, 7]).output, vec![7]);
+    }
+
+    #[test]
+    fn write_position() {
+        assert_eq!(run(vec![1101, 2, 3, 5, 99, 0]).memory[5], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index should never be in immediate mode")]
+    fn write_immediate() {
+        run(vec![11101, 2, 3, 5, 99, 0]);
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+
+use permutohedron::Heap;
+use queues::*;
+
+use crate::Computer;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    // each amplifier runs once and feeds the next, part 1
+    Linear,
+    // the last amplifier feeds the first until they all halt, part 2
+    Feedback,
+}
+
+impl Mode {
+    // the puzzle's phase settings for each mode
+    pub fn default_phases(&self) -> Vec<i32> {
+        match self {
+            Mode::Linear => vec![0, 1, 2, 3, 4],
+            Mode::Feedback => vec![5, 6, 7, 8, 9],
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "linear" => Ok(Mode::Linear),
+            "feedback" => Ok(Mode::Feedback),
+            _ => Err(format!("Unknown mode '{}', expected linear or feedback", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting {
+    pub phases: Vec<i32>,
+    pub signal: i32,
+}
+
+// (index, signal) of the best and worst settings seen
+type Extremes = ((usize, i32), (usize, i32));
+
+#[derive(Debug)]
+pub struct Report {
+    pub best: Setting,
+    pub worst: Setting,
+    pub evaluated: usize,
+}
+
+// runs a chain of one amplifier per phase and returns the signal sent to the thrusters,
+// None for a chain with no amplifiers
+pub fn run_chain(program: &[i32], phases: &[i32], mode: Mode) -> Option<i32> {
+    if phases.is_empty() {
+        return None;
+    }
+    let mut cpus: Vec<Computer> = phases.iter().map(|phase| {
+        let mut cpu = Computer::from_memory(program.to_vec());
+        cpu.input.add(*phase).expect("failed to add phase setting");
+        cpu
+    }).collect();
+    let mut signal = 0;
+    loop {
+        let mut last_halted = false;
+        for (i, cpu) in cpus.iter_mut().enumerate() {
+            cpu.input.add(signal).expect("failed to add to input");
+            last_halted = cpu.run();
+            signal = cpu.output.remove().unwrap_or_else(|_| panic!("amplifier {} produced no output", i));
+        }
+        // run until we halt on the last amplifier
+        if mode == Mode::Linear || last_halted {
+            return Some(signal);
+        }
+    }
+}
+
+// every way of choosing count phases from the set, in order, without repeats
+pub fn settings(phase_set: &[i32], count: usize) -> Vec<Vec<i32>> {
+    fn choose(rest: &[i32], count: usize, chosen: &mut Vec<i32>, out: &mut Vec<Vec<i32>>) {
+        if chosen.len() == count {
+            let mut phases = chosen.clone();
+            out.extend(Heap::new(&mut phases));
+            return;
+        }
+        for (i, phase) in rest.iter().enumerate() {
+            chosen.push(*phase);
+            choose(&rest[i + 1..], count, chosen, out);
+            chosen.pop();
+        }
+    }
+    let mut out = Vec::new();
+    if count <= phase_set.len() {
+        choose(phase_set, count, &mut Vec::new(), &mut out);
+    }
+    out
+}
+
+// tries every setting of count amplifiers, on threads workers, None if there are no settings
+pub fn optimize(program: &[i32], phase_set: &[i32], count: usize, mode: Mode, threads: usize) -> Option<Report> {
+    let candidates = settings(phase_set, count);
+    let next = AtomicUsize::new(0);
+    // ties go to the earliest setting, so the result doesn't depend on scheduling
+    let results: Mutex<Option<Extremes>> = Mutex::new(None);
+
+    thread::scope(|s| {
+        for _ in 0..threads.max(1) {
+            s.spawn(|| {
+                let mut local: Option<Extremes> = None;
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= candidates.len() {
+                        break;
+                    }
+                    if let Some(signal) = run_chain(program, &candidates[i], mode) {
+                        local = Some(merge(local, ((i, signal), (i, signal))));
+                    }
+                }
+                if let Some(local) = local {
+                    let mut results = results.lock().unwrap();
+                    *results = Some(merge(*results, local));
+                }
+            });
+        }
+    });
+
+    let ((best, best_signal), (worst, worst_signal)) = results.into_inner().unwrap()?;
+    Some(Report {
+        best: Setting { phases: candidates[best].clone(), signal: best_signal },
+        worst: Setting { phases: candidates[worst].clone(), signal: worst_signal },
+        evaluated: candidates.len(),
+    })
+}
+
+fn merge(a: Option<Extremes>, b: Extremes) -> Extremes {
+    let a = match a {
+        Some(a) => a,
+        None => return b,
+    };
+    let best = if (b.0).1 > (a.0).1 || ((b.0).1 == (a.0).1 && (b.0).0 < (a.0).0) { b.0 } else { a.0 };
+    let worst = if (b.1).1 < (a.1).1 || ((b.1).1 == (a.1).1 && (b.1).0 < (a.1).0) { b.1 } else { a.1 };
+    (best, worst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    // an example program from the puzzle with its best phase setting and the signal it gives,
+    // checked through both a single chain and the optimizer
+    fn check(mode: Mode, text: &str, phases: [i32; 5], expected: i32) {
+        let memory = parse_program(text);
+        assert_eq!(run_chain(&memory, &phases, mode), Some(expected));
+        let report = optimize(&memory, &mode.default_phases(), phases.len(), mode, 4).unwrap();
+        assert_eq!(report.best.signal, expected);
+        assert_eq!(report.best.phases, phases.to_vec());
+    }
+
+    #[test]
+    fn linear_43210() {
+        check(Mode::Linear, "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0", [4, 3, 2, 1, 0], 43210);
+    }
+
+    #[test]
+    fn linear_54321() {
+        check(Mode::Linear, "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0", [0, 1, 2, 3, 4], 54321);
+    }
+
+    #[test]
+    fn linear_65210() {
+        check(Mode::Linear, "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0",
+            [1, 0, 4, 3, 2], 65210);
+    }
+
+    #[test]
+    fn feedback_139629729() {
+        check(Mode::Feedback, "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+            [9, 8, 7, 6, 5], 139629729);
+    }
+
+    #[test]
+    fn feedback_18216() {
+        check(Mode::Feedback, "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,\
+            1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10",
+            [9, 7, 8, 5, 6], 18216);
+    }
+
+    #[test]
+    fn empty_chain() {
+        let memory = parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        assert_eq!(run_chain(&memory, &[], Mode::Feedback), None);
+        assert!(optimize(&memory, &Mode::Feedback.default_phases(), 0, Mode::Feedback, 4).is_none());
+        assert!(optimize(&memory, &[], 0, Mode::Linear, 4).is_none());
+    }
+}
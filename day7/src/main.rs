@@ -1,9 +1,13 @@
 extern crate queues;
 extern crate permutohedron;
 
+mod amplifiers;
+
+use std::env;
 use std::fs;
+use std::thread;
 use queues::*;
-use permutohedron::Heap;
+use amplifiers::Mode;
 
 
 enum Opcode {
@@ -33,6 +37,20 @@ impl Opcode {
             _ => panic!("Unknown opcode {}", i),
         }
     }
+
+    fn param_count(&self) -> u32 {
+        match self {
+            Opcode::ADD => 3,
+            Opcode::MUL => 3,
+            Opcode::INP => 1,
+            Opcode::OUT => 1,
+            Opcode::JIT => 2,
+            Opcode::JIF => 2,
+            Opcode::LT => 3,
+            Opcode::EQ => 3,
+            Opcode::HALT => 0,
+        }
+    }
 }
 
 enum ParamMode {
@@ -62,6 +80,14 @@ impl Parameter {
             ParamMode::IMMEDIATE => self.value,
         }
     }
+
+    // the address a parameter that is written to refers to
+    fn get_idx(&self) -> usize {
+        match self.mode {
+            ParamMode::POSITION => self.value as usize,
+            ParamMode::IMMEDIATE => panic!("Index should never be in immediate mode"),
+        }
+    }
 }
 
 struct Instruction {
@@ -75,59 +101,21 @@ impl Instruction {
     }
 
     fn new(icode: i32, memory: &Vec<i32>, index: usize) -> Self {
+        // parse the opcode from the instruction code
         let opcode = Opcode::from_i32(icode % 100);
-        match opcode {
-            Opcode::ADD | Opcode::MUL | Opcode::LT | Opcode::EQ => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: vec![
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 100) % 10),
-                            value: memory[index + 1],
-                        },
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 1000) % 10),
-                            value: memory[index + 2],
-                        },
-                        Parameter {
-                            mode: ParamMode::IMMEDIATE,
-                            value: memory[index + 3],
-                        },
-                    ],
-                }
-            },
-            Opcode::INP | Opcode::OUT => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: vec![
-                        Parameter {
-                            mode: ParamMode::IMMEDIATE,
-                            value: memory[index + 1],
-                        },
-                    ],
-                }
-            },
-            Opcode::JIT | Opcode::JIF => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: vec![
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 100) % 10),
-                            value: memory[index + 1],
-                        },
-                        Parameter {
-                            mode: ParamMode::from_i32((icode / 1000) % 10),
-                            value: memory[index + 2],
-                        },
-                    ],
-                }
-            },
-            Opcode::HALT => {
-                Instruction {
-                    opcode: opcode,
-                    parameters: Vec::new(),
-                }
-            },
+        // get the param modes and values for each param in the instruction,
+        // including the ones that are written to
+        let param_count = opcode.param_count();
+        let mut params = Vec::new();
+        for i in 0..param_count {
+            params.push(Parameter {
+                mode: ParamMode::from_i32((icode / (100 * 10i32.pow(i))) % 10),
+                value: memory[index + 1 + i as usize],
+            });
+        }
+        Instruction {
+            opcode,
+            parameters: params,
         }
     }
 }
@@ -140,16 +128,9 @@ struct Computer {
 }
 
 impl Computer {
-    fn new(file_path: &str) -> Self {
+    fn from_memory(memory: Vec<i32>) -> Self {
         Computer {
-            memory: fs::read_to_string(file_path)
-                    .expect("Unable to read file")
-                    .lines()
-                    .next()
-                    .expect("Invalid input")
-                    .split(",")
-                    .map(|x| x.parse::<i32>().expect("Unable to parse"))
-                    .collect(),
+            memory,
             input: Queue::new(),
             output: Queue::new(),
             inst_pointer: 0,
@@ -165,25 +146,25 @@ impl Computer {
                 Opcode::ADD => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     self.memory[idx] = val0 + val1;
                 },
                 Opcode::MUL => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     self.memory[idx] = val0 * val1;
                 },
                 Opcode::INP => {
                     if self.input.size() < 1 {
                         return false; // we have not halted but we have run out of input
                     }
-                    let idx = inst.parameters[0].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[0].get_idx();
                     self.memory[idx] = self.input.remove().expect("Input queue is empty");
                 },
                 Opcode::OUT => {
-                    let idx = inst.parameters[0].get_value(&self.memory) as usize;
-                    self.output.add(self.memory[idx]).expect("failed to add to output queue");
+                    let outp = inst.parameters[0].get_value(&self.memory);
+                    self.output.add(outp).expect("failed to add to output queue");
                 },
                 Opcode::JIT => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
@@ -204,7 +185,7 @@ impl Computer {
                 Opcode::LT => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     if val0 < val1 {
                         self.memory[idx] = 1;
                     } else {
@@ -214,7 +195,7 @@ impl Computer {
                 Opcode::EQ => {
                     let val0 = inst.parameters[0].get_value(&self.memory);
                     let val1 = inst.parameters[1].get_value(&self.memory);
-                    let idx = inst.parameters[2].get_value(&self.memory) as usize;
+                    let idx = inst.parameters[2].get_idx();
                     if val0 == val1 {
                         self.memory[idx] = 1;
                     } else {
@@ -231,42 +212,89 @@ impl Computer {
     }
 }
 
-fn run_amplifiers(phase_settings: &Vec<i32>) -> i32 {
-    // create the 5 amplifiers
-    let mut cpus: Vec<Computer> = Vec::new();
-    for i in 0..5 {
-        let mut cpu = Computer::new("input.txt");
-        cpu.input.add(phase_settings[i]).expect("failed to add phase setting");
-        cpus.push(cpu);
-    }
-    // run the amplifier loop until we halt
-    let mut last_output = 0;
-    let mut halt_count = 0;
-    'outer: loop {
-        for i in 0..5 {
-            let cpu = &mut cpus[i];
-            cpu.input.add(last_output).expect("failed to add to input");
-            if cpu.run() {
-                halt_count += 1;
-            }
-            last_output = cpu.output.remove().expect("failed to remove from output");
-            if halt_count == 5 { // run until we halt on the last amplifer
-                break 'outer; 
-            }
+fn parse_program(text: &str) -> Vec<i32> {
+    text.lines()
+        .next()
+        .expect("Invalid input")
+        .split(",")
+        .map(|x| x.trim().parse::<i32>().expect("Unable to parse"))
+        .collect()
+}
+
+fn format_phases(phases: &[i32]) -> String {
+    phases.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(",")
+}
+
+// [program] [--mode linear|feedback] [--phases 5,6,7,8,9] [--amps n] [--threads n]
+// Without --mode both parts of the puzzle are solved.
+fn main() {
+    let mut program = String::from("input.txt");
+    let mut modes = vec![Mode::Linear, Mode::Feedback];
+    let mut phases: Option<Vec<i32>> = None;
+    let mut amps: Option<usize> = None;
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mode" => modes = vec![Mode::parse(&args.next().expect("--mode needs linear or feedback")).unwrap_or_else(|e| panic!("{}", e))],
+            "--phases" => phases = Some(args.next().expect("--phases needs a list of phases")
+                .split(",")
+                .map(|p| p.trim().parse().expect("Invalid phase"))
+                .collect()),
+            "--amps" => amps = Some(args.next().and_then(|n| n.parse().ok()).expect("--amps needs a number")),
+            "--threads" => threads = args.next().and_then(|n| n.parse().ok()).expect("--threads needs a number"),
+            _ => program = arg,
         }
     }
-    return last_output;
+    let memory = parse_program(&fs::read_to_string(&program).expect("Unable to read file"));
+    for mode in modes {
+        let phases = phases.clone().unwrap_or_else(|| mode.default_phases());
+        let amps = amps.unwrap_or(phases.len());
+        let report = amplifiers::optimize(&memory, &phases, amps, mode, threads)
+            .unwrap_or_else(|| panic!("Can't choose {} amplifier phases from {}", amps, format_phases(&phases)));
+        println!("{:?} mode, evaluated {} settings", mode, report.evaluated);
+        println!("Best: {} -> {}", format_phases(&report.best.phases), report.best.signal);
+        println!("Worst: {} -> {}", format_phases(&report.worst.phases), report.worst.signal);
+        println!("Max Thrust: {}", report.best.signal);
+    }
 }
 
-fn main() {
-    let mut max_thrust = 0;
-    let mut phases = vec![5, 6, 7, 8, 9];
-    let phases_perms = Heap::new(&mut phases);
-    for pperm in phases_perms {
-        let new_thrust = run_amplifiers(&pperm);
-        if new_thrust > max_thrust {
-            max_thrust = new_thrust;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(memory: Vec<i32>) -> Computer {
+        let mut comp = Computer::from_memory(memory);
+        comp.run();
+        comp
+    }
+
+    fn outputs(comp: &mut Computer) -> Vec<i32> {
+        let mut values = Vec::new();
+        while let Ok(v) = comp.output.remove() {
+            values.push(v);
         }
+        values
+    }
+
+    #[test]
+    fn out_immediate() {
+        assert_eq!(outputs(&mut run(vec![104, 42, 99])), vec![42]);
+    }
+
+    #[test]
+    fn out_position() {
+        assert_eq!(outputs(&mut run(vec![4, 3, 99, 7])), vec![7]);
+    }
+
+    #[test]
+    fn write_position() {
+        assert_eq!(run(vec![1101, 2, 3, 5, 99, 0]).memory[5], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index should never be in immediate mode")]
+    fn write_immediate() {
+        run(vec![11101, 2, 3, 5, 99, 0]);
     }
-    println!("Max Thrust: {}", max_thrust);
 }
@@ -1,117 +1,148 @@
+use std::env;
 use std::fs::read_to_string;
-use std::u32;
+use aoc::export;
+use aoc::export::ExportOptions;
+use aoc::export::Palette;
+use aoc::ocr::Font;
 
-const BLACK: u32 = 0;
-const WHITE: u32 = 1;
-const TRANSPARENT: u32 = 2;
+mod report;
+mod sif;
 
-fn layer_count(width: usize, height: usize, data: &Vec<u32>) -> usize {
-    data.len() / (width * height)
-}
+use report::Report;
 
-fn count_digit_occurence(digit: u32, data: &Vec<u32>) -> u32 {
-    let mut count = 0;
-    for x in data.iter() {
-        if *x == digit {
-            count += 1;
-        }
-    }
-    return count;
-}
+use sif::Image;
+use sif::BLACK;
+use sif::TRANSPARENT;
+use sif::WHITE;
 
 #[allow(dead_code)]
-fn print_layer(width: usize, height: usize, data: &Vec<u32>) {
-    for i in 0..height {
-        for j in 0..width {
-            print!("{}", data[height * i + j]);
+fn print_layer(image: &Image, layer: &[u8]) {
+    for row in image.rows(layer) {
+        for px in row.iter() {
+            print!("{}", px);
         }
-        println!("");
+        println!();
     }
 }
 
-fn flatten_layers(layers: &Vec<Vec<u32>>) -> Vec<u32> {
-    let mut flat = vec![TRANSPARENT; layers.first().unwrap().len()];
-    for layer in layers.iter() {
-        for (i, px) in layer.iter().enumerate() {
-            if flat[i] == TRANSPARENT && *px != TRANSPARENT {
-                flat[i] = *px;
-            }
-        }
-    }
-
-    return flat;
-}
-
-fn render_layer(width: usize, height: usize, data: &Vec<u32>) {
+fn render_layer(image: &Image, layer: &[u8]) {
     // render the final image
-    for i in 0..height {
-        for j in 0..width {
-            match data[width * i + j] {
+    for row in image.rows(layer) {
+        for px in row.iter() {
+            match *px {
                 BLACK => print!("  "),
                 WHITE => print!("██"),
                 TRANSPARENT | _ => print!("  "),
             }
         }
-        println!("");
+        println!();
     }
 }
 
-fn parse_input(file_path: &str) -> Vec<u32> {
-    return read_to_string(file_path)
-    .expect("unable to read file")
-    .trim()
-    .chars()
-    .map(|c| c.to_digit(10).expect("failed to parse char {}"))
-    .collect();
+fn parse_input(file_path: &str) -> String {
+    read_to_string(file_path).expect("unable to read file")
 }
 
-fn split_layers(width: usize, height: usize, data: &Vec<u32>) -> Vec<Vec<u32>> {
-    let mut layers: Vec<Vec<u32>> = Vec::new();
-    // iterate over every layer
-    for li in 0..layer_count(width, height, &data) {
-        let mut layer: Vec<u32> = Vec::new();
-        // iterate over every pixel in the layer
-        for i in 0..(width * height) {
-            // calculate the actual pixel index in data vec
-            let pi = (width * height * li) + i;
-            layer.push(data[pi]);
-        }
-        layers.push(layer);
+// parses a size like "25x6"
+fn parse_size(text: &str) -> Option<(usize, usize)> {
+    let mut parts = text.split('x');
+    let width = parts.next()?.parse().ok()?;
+    let height = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
     }
-    return layers;
+    Some((width, height))
+}
+
+fn part1(image: &Image) -> usize {
+    // 1's times 2's on the layer with the fewest 0's
+    image.checksum()
 }
 
-fn part1(layers: &Vec<Vec<u32>>) -> u32 {
-    // find layer with fewest 0's
-    let mut fewest_index = 0;
-    let mut fewest_count = u32::MAX;
-    for (i, layer) in layers.iter().enumerate() {
-        let new_count = count_digit_occurence(0, &layer);
-        if new_count < fewest_count {
-            fewest_count = new_count;
-            fewest_index = i;
+// [--size 25x6] [--report table|json] [--encode out.txt] [--font table] [--export image.png|.ppm|.svg] [--scale n] [--palette 000000,ffffff,808080]
+// --size is the width and height of each layer, --report prints statistics for every layer instead of the answers,
+// --encode writes the composed image back out as a single layer SIF file,
+// --font reads the message with a different font table than the standard one,
+// --export writes the final image, with a palette colour for black, white and transparent
+fn main() {
+    let mut width = 25;
+    let mut height = 6;
+    let mut report_format: Option<String> = None;
+    let mut encode_path: Option<String> = None;
+    let mut font = Font::standard();
+    let mut export_path: Option<String> = None;
+    let mut export_options = ExportOptions::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let size = args.next().and_then(|s| parse_size(&s)).expect("--size needs a size like 25x6");
+                width = size.0;
+                height = size.1;
+            },
+            "--report" => report_format = Some(args.next().expect("--report needs table or json")),
+            "--encode" => encode_path = Some(args.next().expect("--encode needs a path")),
+            "--font" => {
+                let path = args.next().expect("--font needs a path");
+                let table = read_to_string(&path).expect("unable to read font table");
+                font = Font::parse(&table).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            },
+            "--export" => export_path = Some(args.next().expect("--export needs a path")),
+            "--scale" => export_options.scale = args.next().and_then(|n| n.parse().ok()).expect("--scale needs a number"),
+            "--palette" => {
+                let spec = args.next().expect("--palette needs a list of colours");
+                export_options.palette = Palette::parse(&spec).unwrap_or_else(|e| panic!("{}", e));
+            },
+            _ => panic!("Unknown argument {}", arg),
         }
     }
 
-    // get number of 1's and 2's
-    let count_1 = count_digit_occurence(1, &layers[fewest_index]);
-    let count_2 = count_digit_occurence(2, &layers[fewest_index]);
-    return count_1 * count_2;
-}
+    // read input and split the image into layers
+    let image = Image::decode(width, height, &parse_input("input.txt")).unwrap_or_else(|e| panic!("input.txt: {}", e));
 
-fn main() {
-    let width = 25;
-    let height = 6;
-    // read input
-    let data = parse_input("input.txt");
-    // split image into layers
-    let layers = split_layers(width, height, &data);
+    if let Some(format) = report_format {
+        let report = Report::of(&image);
+        match format.as_str() {
+            "table" => print!("{}", report.to_table()),
+            "json" => println!("{}", report.to_json()),
+            _ => panic!("Unknown report format {}, expected table or json", format),
+        }
+        return;
+    }
 
     // part 1
-    let p1_ans = part1(&layers);
+    let p1_ans = part1(&image);
     println!("Part1 Answer: {}", p1_ans);
 
     // part 2
-    let final_img = flatten_layers(&layers);
-    render_layer(width, height, &final_img);
+    let final_img = image.flatten();
+    render_layer(&image, &final_img);
+    let pixels: Vec<Vec<bool>> = final_img.chunks(image.width()).map(|row| row.iter().map(|px| *px == WHITE).collect()).collect();
+    match font.read(&pixels) {
+        Ok(text) => println!("Part2 Answer: {}", text),
+        Err(e) => println!("Unable to read the message: {}", e),
+    }
+    if let Some(path) = export_path {
+        let grid: Vec<Vec<usize>> = final_img.chunks(image.width()).map(|row| row.iter().map(|px| *px as usize).collect()).collect();
+        export::export(&path, &grid, &export_options).unwrap_or_else(|e| panic!("{}", e));
+        println!("Image written to {}", path);
+    }
+    if let Some(path) = encode_path {
+        let composed = Image::from_pixels(image.width(), image.height(), &final_img).unwrap_or_else(|e| panic!("{}", e));
+        std::fs::write(&path, composed.encode()).unwrap_or_else(|e| panic!("Unable to write {}: {}", path, e));
+        println!("Composed image written to {}", path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        let image = Image::decode(25, 6, &parse_input("input.txt")).unwrap();
+        assert_eq!(part1(&image), 1806);
+        let pixels: Vec<Vec<bool>> = image.flatten().chunks(image.width()).map(|row| row.iter().map(|px| *px == WHITE).collect()).collect();
+        assert_eq!(Font::standard().read(&pixels).unwrap(), "JAFRA");
+    }
 }
@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use crate::sif::Image;
+use crate::sif::LayerStats;
+
+// the layers with the fewest and most of a digit, the first layer wins a tie
+pub struct Extremes {
+    pub digit: u8,
+    pub min_layer: usize,
+    pub min: usize,
+    pub max_layer: usize,
+    pub max: usize,
+}
+
+// statistics across every layer of an image, for checking images from somewhere other than the puzzle
+pub struct Report {
+    pub width: usize,
+    pub height: usize,
+    pub checksum: usize,
+    pub layers: Vec<LayerStats>,
+    // only digits that appear somewhere in the image
+    pub extremes: Vec<Extremes>,
+    // how many layers are transparent at each pixel, a row at a time
+    pub transparency: Vec<usize>,
+}
+
+impl Report {
+    pub fn of(image: &Image) -> Self {
+        let layers = image.stats();
+        let mut extremes = Vec::new();
+        for digit in 0..10 {
+            if layers.iter().all(|s| s.count(digit) == 0) {
+                continue;
+            }
+            let mut e = Extremes { digit, min_layer: 0, min: layers[0].count(digit), max_layer: 0, max: layers[0].count(digit) };
+            for (i, s) in layers.iter().enumerate().skip(1) {
+                if s.count(digit) < e.min {
+                    e.min_layer = i;
+                    e.min = s.count(digit);
+                }
+                if s.count(digit) > e.max {
+                    e.max_layer = i;
+                    e.max = s.count(digit);
+                }
+            }
+            extremes.push(e);
+        }
+        Report {
+            width: image.width(),
+            height: image.height(),
+            checksum: image.checksum(),
+            layers,
+            extremes,
+            transparency: image.transparency(),
+        }
+    }
+
+    // pixels that are transparent on every layer, so stay transparent once composed
+    pub fn see_through(&self) -> usize {
+        self.transparency.iter().filter(|t| **t == self.layers.len()).count()
+    }
+
+    pub fn to_table(&self) -> String {
+        let digits: Vec<u8> = self.extremes.iter().map(|e| e.digit).collect();
+        let mut out = String::new();
+        writeln!(out, "Image: {}x{}, {} layers", self.width, self.height, self.layers.len()).unwrap();
+        writeln!(out, "Checksum: {}", self.checksum).unwrap();
+
+        writeln!(out).unwrap();
+        write!(out, "{:>6}", "layer").unwrap();
+        for digit in digits.iter() {
+            write!(out, "{:>6}", format!("{}s", digit)).unwrap();
+        }
+        writeln!(out).unwrap();
+        for (i, s) in self.layers.iter().enumerate() {
+            write!(out, "{:>6}", i).unwrap();
+            for digit in digits.iter() {
+                write!(out, "{:>6}", s.count(*digit)).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        writeln!(out).unwrap();
+        writeln!(out, "{:>6}{:>16}{:>16}", "digit", "fewest (layer)", "most (layer)").unwrap();
+        for e in self.extremes.iter() {
+            writeln!(out, "{:>6}{:>16}{:>16}", e.digit, format!("{} ({})", e.min, e.min_layer), format!("{} ({})", e.max, e.max_layer)).unwrap();
+        }
+
+        writeln!(out).unwrap();
+        writeln!(out, "Transparent layers per pixel:").unwrap();
+        let pad = self.layers.len().to_string().len() + 1;
+        for row in self.transparency.chunks(self.width) {
+            for t in row.iter() {
+                write!(out, "{:>1$}", t, pad).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        writeln!(out, "Pixels transparent on every layer: {}", self.see_through()).unwrap();
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |values: &[usize]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", ");
+        let layers: Vec<String> = self.layers.iter().map(|s| format!("[{}]", list(s.counts()))).collect();
+        let extremes: Vec<String> = self.extremes.iter()
+            .map(|e| format!("{{\"digit\": {}, \"min\": {}, \"min_layer\": {}, \"max\": {}, \"max_layer\": {}}}",
+                e.digit, e.min, e.min_layer, e.max, e.max_layer))
+            .collect();
+        format!("{{\"width\": {}, \"height\": {}, \"checksum\": {}, \"histograms\": [{}], \"extremes\": [{}], \"transparency\": [{}], \"see_through\": {}}}",
+            self.width, self.height, self.checksum, layers.join(", "), extremes.join(", "), list(&self.transparency), self.see_through())
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+// pixel colours, any other digit is a valid pixel but has no colour
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 1;
+pub const TRANSPARENT: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SifError {
+    // a width or height of 0
+    NoSize,
+    // a character that isn't a digit, position is 0 based
+    InvalidDigit { position: usize, found: char },
+    // a composed pixel above 9, which can't be written as a digit
+    InvalidPixel { position: usize, value: u8 },
+    // the data ends part way through a layer
+    Truncated { length: usize, layer_size: usize },
+    // no layers at all
+    Empty,
+    // composed pixels that don't fill exactly one layer
+    WrongSize { expected: usize, found: usize },
+}
+
+impl fmt::Display for SifError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SifError::NoSize => write!(f, "width and height must both be at least 1"),
+            SifError::InvalidDigit { position, found } => write!(f, "'{}' at position {} is not a digit", found, position),
+            SifError::InvalidPixel { position, value } => write!(f, "pixel {} is {}, pixels must be 0 to 9", position, value),
+            SifError::Truncated { length, layer_size } =>
+                write!(f, "{} digits is not a whole number of {} digit layers, the last layer has {}", length, layer_size, length % layer_size),
+            SifError::Empty => write!(f, "the image has no layers"),
+            SifError::WrongSize { expected, found } => write!(f, "expected {} pixels, found {}", expected, found),
+        }
+    }
+}
+
+// how many of each digit a layer has
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerStats {
+    counts: [usize; 10],
+}
+
+impl LayerStats {
+    pub fn of(layer: &[u8]) -> Self {
+        let mut counts = [0; 10];
+        for px in layer.iter() {
+            counts[*px as usize] += 1;
+        }
+        LayerStats { counts }
+    }
+
+    pub fn count(&self, digit: u8) -> usize {
+        self.counts[digit as usize]
+    }
+
+    pub fn counts(&self) -> &[usize; 10] {
+        &self.counts
+    }
+}
+
+// A Space Image Format image, layers of width * height digits, the first layer in front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    layers: Vec<Vec<u8>>,
+}
+
+impl Image {
+    // decodes a string of digits, whitespace at either end is ignored
+    pub fn decode(width: usize, height: usize, text: &str) -> Result<Self, SifError> {
+        if width == 0 || height == 0 {
+            return Err(SifError::NoSize);
+        }
+        let text = text.trim();
+        let mut data = Vec::with_capacity(text.len());
+        for (position, c) in text.chars().enumerate() {
+            let digit = c.to_digit(10).ok_or(SifError::InvalidDigit { position, found: c })?;
+            data.push(digit as u8);
+        }
+        if data.is_empty() {
+            return Err(SifError::Empty);
+        }
+        let layer_size = width * height;
+        if data.len() % layer_size != 0 {
+            return Err(SifError::Truncated { length: data.len(), layer_size });
+        }
+        let layers = data.chunks(layer_size).map(|l| l.to_vec()).collect();
+        Ok(Image { width, height, layers })
+    }
+
+    // a single layer image from composed pixels, a row at a time
+    pub fn from_pixels(width: usize, height: usize, pixels: &[u8]) -> Result<Self, SifError> {
+        if width == 0 || height == 0 {
+            return Err(SifError::NoSize);
+        }
+        if pixels.len() != width * height {
+            return Err(SifError::WrongSize { expected: width * height, found: pixels.len() });
+        }
+        if let Some(position) = pixels.iter().position(|px| *px > 9) {
+            return Err(SifError::InvalidPixel { position, value: pixels[position] });
+        }
+        Ok(Image { width, height, layers: vec![pixels.to_vec()] })
+    }
+
+    // the digits of every layer, front to back, the same way decode reads them
+    pub fn encode(&self) -> String {
+        self.layers.iter().flatten().map(|px| (b'0' + px) as char).collect()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn stats(&self) -> Vec<LayerStats> {
+        self.layers.iter().map(|l| LayerStats::of(l)).collect()
+    }
+
+    // the number of 1s times the number of 2s on the layer with the fewest 0s
+    pub fn checksum(&self) -> usize {
+        let stats = self.stats();
+        let fewest = stats.iter().min_by_key(|s| s.count(0)).unwrap();
+        fewest.count(1) * fewest.count(2)
+    }
+
+    // how many layers are transparent at each pixel
+    pub fn transparency(&self) -> Vec<usize> {
+        let mut coverage = vec![0; self.width * self.height];
+        for layer in self.layers.iter() {
+            for (i, px) in layer.iter().enumerate() {
+                if *px == TRANSPARENT {
+                    coverage[i] += 1;
+                }
+            }
+        }
+        coverage
+    }
+
+    // each pixel takes the colour of the first layer that isn't transparent there
+    pub fn flatten(&self) -> Vec<u8> {
+        let mut flat = vec![TRANSPARENT; self.width * self.height];
+        for layer in self.layers.iter() {
+            for (i, px) in layer.iter().enumerate() {
+                if flat[i] == TRANSPARENT && *px != TRANSPARENT {
+                    flat[i] = *px;
+                }
+            }
+        }
+        flat
+    }
+
+    // a layer's pixels as rows
+    pub fn rows<'a>(&self, layer: &'a [u8]) -> Vec<&'a [u8]> {
+        layer.chunks(self.width).collect()
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// compiled intcode stores a return address (usually relative to the relative base)
+// and then jumps to the function, which later jumps back through that stored address
+pub struct Frame {
+    pub entry: usize,
+    pub call_site: usize,
+    pub return_addr: usize,
+    pub relative_base: usize,
+}
+
+#[derive(Default)]
+pub struct FunctionStats {
+    pub calls: u64,
+    pub returns: u64,
+    // returns where the relative base was not restored to its value at the call
+    pub unbalanced: u64,
+    pub max_depth: usize,
+}
+
+pub struct CallStack {
+    frames: Vec<Frame>,
+    // values written since the last taken jump, candidates for a stored return address
+    written: Vec<i64>,
+    functions: BTreeMap<usize, FunctionStats>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        CallStack {
+            frames: Vec::new(),
+            written: Vec::new(),
+            functions: BTreeMap::new(),
+        }
+    }
+
+    pub fn record_write(&mut self, value: i64) {
+        self.written.push(value);
+    }
+
+    // returns true if the jump was recognised as a call or a return
+    pub fn record_jump(&mut self, from: usize, next: usize, target: usize, relative_base: usize) -> bool {
+        let is_call = self.written.contains(&(next as i64));
+        self.written.clear();
+
+        // a jump back to a pending return address unwinds every frame above it
+        if let Some(pos) = self.frames.iter().rposition(|f| f.return_addr == target) {
+            while self.frames.len() > pos {
+                let frame = self.frames.pop().unwrap();
+                let stats = self.functions.entry(frame.entry).or_default();
+                stats.returns += 1;
+                if frame.relative_base != relative_base {
+                    stats.unbalanced += 1;
+                }
+            }
+            return true;
+        }
+
+        if is_call {
+            self.frames.push(Frame {
+                entry: target,
+                call_site: from,
+                return_addr: next,
+                relative_base,
+            });
+            let depth = self.frames.len();
+            let stats = self.functions.entry(target).or_default();
+            stats.calls += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            return true;
+        }
+        false
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    // innermost frame first, in the style of gdb's bt
+    pub fn backtrace(&self, inst_pointer: usize) -> String {
+        let mut out = String::new();
+        let mut ip = inst_pointer;
+        for (i, frame) in self.frames.iter().rev().enumerate() {
+            writeln!(out, "#{:<3} ip {:>6} in fn@{} (rb {})", i, ip, frame.entry, frame.relative_base).unwrap();
+            ip = frame.call_site;
+        }
+        writeln!(out, "#{:<3} ip {:>6} in main", self.frames.len(), ip).unwrap();
+        out
+    }
+
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{:>8} {:>10} {:>10} {:>10} {:>9}", "entry", "calls", "returns", "unbalanced", "max depth").unwrap();
+        for (entry, stats) in self.functions.iter() {
+            writeln!(out, "{:>8} {:>10} {:>10} {:>10} {:>9}", entry, stats.calls, stats.returns, stats.unbalanced, stats.max_depth).unwrap();
+        }
+        out
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt::Write;
+
+use crate::disasm::StaticInst;
+use crate::optimizer;
+use crate::Opcode;
+use crate::ParamMode;
+
+// the part of the generated module that doesn't depend on the program
+const RUNTIME: &str = r#"
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    Halted,
+    NeedInput,
+}
+
+pub struct Machine {
+    pub memory: Vec<i64>,
+    pub ip: usize,
+    pub rb: i64,
+    pub input: VecDeque<i64>,
+    pub output: Vec<i64>,
+    // cleared as soon as the program writes over one of its compiled instructions
+    compiled: bool,
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        Machine {
+            memory: IMAGE.to_vec(),
+            ip: 0,
+            rb: 0,
+            input: VecDeque::new(),
+            output: Vec::new(),
+            compiled: true,
+        }
+    }
+
+    fn rd(&mut self, addr: i64) -> i64 {
+        let addr = addr as usize;
+        if self.memory.len() <= addr {
+            self.memory.resize(addr + 10, 0);
+        }
+        self.memory[addr]
+    }
+
+    fn wr(&mut self, addr: i64, value: i64) {
+        let addr = addr as usize;
+        if self.memory.len() <= addr {
+            self.memory.resize(addr + 10, 0);
+        }
+        if addr < CODE.len() && CODE[addr] {
+            self.compiled = false;
+        }
+        self.memory[addr] = value;
+    }
+
+    pub fn run(&mut self) -> State {
+        loop {
+            if self.compiled {
+                if let Some(state) = self.run_compiled() {
+                    return state;
+                }
+            }
+            if let Some(state) = self.step() {
+                return state;
+            }
+        }
+    }
+
+    fn param(&mut self, i: usize) -> (i64, i64) {
+        let icode = self.rd(self.ip as i64);
+        let raw = self.rd((self.ip + 1 + i) as i64);
+        match (icode / (100 * 10i64.pow(i as u32))) % 10 {
+            0 => (self.rd(raw), raw),
+            1 => (raw, raw),
+            2 => (self.rd(self.rb + raw), self.rb + raw),
+            m => panic!("Unknown parameter mode {} at {}", m, self.ip),
+        }
+    }
+
+    // the fallback interpreter, for computed jumps into uncompiled code and self modifying programs
+    fn step(&mut self) -> Option<State> {
+        let icode = self.rd(self.ip as i64);
+        let ip = self.ip;
+        match icode % 100 {
+            1 | 2 | 7 | 8 => {
+                let (a, _) = self.param(0);
+                let (b, _) = self.param(1);
+                let (_, t) = self.param(2);
+                let v = match icode % 100 {
+                    1 => a + b,
+                    2 => a * b,
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+                self.wr(t, v);
+                self.ip = ip + 4;
+            },
+            3 => {
+                let (_, t) = self.param(0);
+                match self.input.pop_front() {
+                    Some(v) => self.wr(t, v),
+                    None => return Some(State::NeedInput),
+                }
+                self.ip = ip + 2;
+            },
+            4 => {
+                let (a, _) = self.param(0);
+                self.output.push(a);
+                self.ip = ip + 2;
+            },
+            5 | 6 => {
+                let (a, _) = self.param(0);
+                let (b, _) = self.param(1);
+                if (a != 0) == (icode % 100 == 5) {
+                    self.ip = b as usize;
+                } else {
+                    self.ip = ip + 3;
+                }
+            },
+            9 => {
+                let (a, _) = self.param(0);
+                self.rb += a;
+                self.ip = ip + 2;
+            },
+            99 => return Some(State::Halted),
+            op => panic!("Unknown opcode {} at {}", op, ip),
+        }
+        None
+    }
+"#;
+
+fn operand(mode: ParamMode, value: i64) -> String {
+    match mode {
+        ParamMode::POSITION => format!("self.rd({})", value),
+        ParamMode::IMMEDIATE => format!("{}", value),
+        ParamMode::RELATIVE => format!("self.rd(self.rb + {})", value),
+    }
+}
+
+fn target(mode: ParamMode, value: i64) -> String {
+    match mode {
+        ParamMode::RELATIVE => format!("self.rb + {}", value),
+        _ => format!("{}", value),
+    }
+}
+
+fn compile_inst(inst: &StaticInst) -> String {
+    let next = inst.addr + inst.len();
+    let p = |i: usize| operand(inst.modes[i], inst.params[i]);
+    let t = |i: usize| target(inst.modes[i], inst.params[i]);
+    match inst.opcode {
+        Opcode::ADD | Opcode::MUL | Opcode::LT | Opcode::EQ => {
+            let expr = match inst.opcode {
+                Opcode::ADD => "a + b",
+                Opcode::MUL => "a * b",
+                Opcode::LT => "(a < b) as i64",
+                _ => "(a == b) as i64",
+            };
+            format!("{{ let a = {}; let b = {}; self.wr({}, {}); self.ip = {}; }}", p(0), p(1), t(2), expr, next)
+        },
+        Opcode::INP => format!(
+            "match self.input.pop_front() {{ Some(v) => {{ self.wr({}, v); self.ip = {}; }}, None => return Some(State::NeedInput) }}",
+            t(0), next),
+        Opcode::OUT => format!("{{ let a = {}; self.output.push(a); self.ip = {}; }}", p(0), next),
+        Opcode::JIT | Opcode::JIF => {
+            let test = if inst.opcode == Opcode::JIT { "!=" } else { "==" };
+            format!("if {} {} 0 {{ self.ip = {} as usize; }} else {{ self.ip = {}; }}", p(0), test, p(1), next)
+        },
+        Opcode::ARB => format!("{{ self.rb += {}; self.ip = {}; }}", p(0), next),
+        Opcode::HALT => "return Some(State::Halted)".to_string(),
+        Opcode::EXT(_) => unreachable!(),
+    }
+}
+
+// translates every statically reachable instruction into a match arm, anything else runs
+// through the fallback interpreter in the generated module
+pub fn compile_to_rust(program: &[i64], with_main: bool) -> String {
+    let reachable = optimizer::reachable(program);
+    let mut code = vec![false; program.len()];
+    for inst in reachable.values() {
+        for cell in code[inst.addr..inst.addr + inst.len()].iter_mut() {
+            *cell = true;
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "// Generated from an intcode program by day9's codegen, do not edit").unwrap();
+    writeln!(out, "#![allow(dead_code, unused_parens, clippy::all)]").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use std::collections::VecDeque;").unwrap();
+    writeln!(out).unwrap();
+    let image: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    writeln!(out, "pub const IMAGE: [i64; {}] = [{}];", program.len(), image.join(", ")).unwrap();
+    let cells: Vec<&str> = code.iter().map(|c| if *c { "true" } else { "false" }).collect();
+    writeln!(out, "const CODE: [bool; {}] = [{}];", code.len(), cells.join(", ")).unwrap();
+    out.push_str(RUNTIME);
+    writeln!(out).unwrap();
+    writeln!(out, "    fn run_compiled(&mut self) -> Option<State> {{").unwrap();
+    writeln!(out, "        while self.compiled {{").unwrap();
+    writeln!(out, "            match self.ip {{").unwrap();
+    for inst in reachable.values() {
+        writeln!(out, "                // {}", inst.to_string().trim()).unwrap();
+        writeln!(out, "                {} => {},", inst.addr, compile_inst(inst)).unwrap();
+    }
+    writeln!(out, "                _ => return None,").unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "        None").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    if with_main {
+        out.push_str(r#"
+// inputs are taken from the command line, outputs are printed one per line
+fn main() {
+    let mut machine = Machine::new();
+    for arg in std::env::args().skip(1) {
+        machine.input.push_back(arg.parse().expect("Invalid input"));
+    }
+    let start = std::time::Instant::now();
+    let state = machine.run();
+    let elapsed = start.elapsed();
+    for value in machine.output.iter() {
+        println!("{}", value);
+    }
+    eprintln!("{:?} after {:?}", state, elapsed);
+}
+"#);
+    }
+    out
+}
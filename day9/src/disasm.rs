@@ -0,0 +1,108 @@
+use std::fmt;
+
+use crate::extensions::OpcodeRegistry;
+use crate::Opcode;
+use crate::ParamMode;
+
+// an instruction decoded from a program image without running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticInst {
+    pub addr: usize,
+    pub opcode: Opcode,
+    pub modes: Vec<ParamMode>,
+    pub params: Vec<i64>,
+}
+
+impl StaticInst {
+    pub fn len(&self) -> usize {
+        self.params.len() + 1
+    }
+
+    // the parameter this instruction writes to, if any
+    pub fn write_param(&self) -> Option<usize> {
+        match self.opcode {
+            Opcode::ADD | Opcode::MUL | Opcode::LT | Opcode::EQ => Some(2),
+            Opcode::INP => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let mut icode = opcode_number(self.opcode);
+        for (i, mode) in self.modes.iter().enumerate() {
+            let digit = match mode {
+                ParamMode::POSITION => 0,
+                ParamMode::IMMEDIATE => 1,
+                ParamMode::RELATIVE => 2,
+            };
+            icode += digit * 100 * 10i64.pow(i as u32);
+        }
+        let mut words = vec![icode];
+        words.extend(self.params.iter());
+        words
+    }
+}
+
+impl fmt::Display for StaticInst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>6}: {:?}", self.addr, self.opcode)?;
+        for (mode, value) in self.modes.iter().zip(self.params.iter()) {
+            match mode {
+                ParamMode::POSITION => write!(f, " [{}]", value)?,
+                ParamMode::IMMEDIATE => write!(f, " {}", value)?,
+                ParamMode::RELATIVE => write!(f, " [rb{:+}]", value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn opcode_number(opcode: Opcode) -> i64 {
+    match opcode {
+        Opcode::ADD => 1,
+        Opcode::MUL => 2,
+        Opcode::INP => 3,
+        Opcode::OUT => 4,
+        Opcode::JIT => 5,
+        Opcode::JIF => 6,
+        Opcode::LT => 7,
+        Opcode::EQ => 8,
+        Opcode::ARB => 9,
+        Opcode::HALT => 99,
+        Opcode::EXT(i) => i,
+    }
+}
+
+// None if the cell doesn't hold a valid built in instruction
+pub fn decode(memory: &[i64], addr: usize) -> Option<StaticInst> {
+    let icode = *memory.get(addr)?;
+    if icode < 0 {
+        return None;
+    }
+    match icode % 100 {
+        1..=9 | 99 => (),
+        _ => return None,
+    }
+    let registry = OpcodeRegistry::new();
+    let opcode = Opcode::from_i64(icode % 100, &registry).ok()?;
+    let count = opcode.param_count(&registry);
+    // any digits beyond the parameter modes make this an invalid instruction
+    if icode / (100 * 10i64.pow(count)) != 0 {
+        return None;
+    }
+    let mut modes = Vec::new();
+    let mut params = Vec::new();
+    for i in 0..count {
+        let digit = (icode / (100 * 10i64.pow(i))) % 10;
+        modes.push(ParamMode::from_i64(digit).ok()?);
+        params.push(*memory.get(addr + 1 + i as usize)?);
+    }
+    let inst = StaticInst { addr, opcode, modes, params };
+    // writes are never made in immediate mode
+    if let Some(w) = inst.write_param() {
+        if inst.modes[w] == ParamMode::IMMEDIATE {
+            return None;
+        }
+    }
+    Some(inst)
+}
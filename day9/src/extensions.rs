@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::Computer;
+use crate::Parameter;
+
+// what the computer should do once an extension opcode has run
+pub enum ExtAction {
+    Continue,
+    Jump(usize),
+    // stop running but leave the computer resumable, like waiting on input
+    Pause,
+    Halt,
+}
+
+pub type Handler = Box<dyn Fn(&mut Computer, &[Parameter]) -> ExtAction>;
+
+pub struct Extension {
+    pub param_count: u32,
+    handler: Handler,
+}
+
+impl Extension {
+    pub fn call(&self, comp: &mut Computer, params: &[Parameter]) -> ExtAction {
+        (self.handler)(comp, params)
+    }
+}
+
+pub struct OpcodeRegistry {
+    extensions: HashMap<i64, Rc<Extension>>,
+}
+
+impl OpcodeRegistry {
+    pub fn new() -> Self {
+        OpcodeRegistry {
+            extensions: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, code: i64, param_count: u32, handler: Handler) -> Result<(), String> {
+        // the opcode is the last two digits of an instruction, the rest are parameter modes
+        if !(10..99).contains(&code) {
+            return Err(format!("Opcode {} is reserved or out of range", code));
+        }
+        if self.extensions.contains_key(&code) {
+            return Err(format!("Opcode {} is already registered", code));
+        }
+        self.extensions.insert(code, Rc::new(Extension {
+            param_count,
+            handler,
+        }));
+        Ok(())
+    }
+
+    pub fn get(&self, code: i64) -> Option<Rc<Extension>> {
+        self.extensions.get(&code).cloned()
+    }
+}
+
+// DBG x: print a value to stderr
+pub fn debug_print() -> Handler {
+    Box::new(|comp, params| {
+        let value = params[0].get_value(comp);
+        eprintln!("[ip {}] {}", comp.inst_pointer, value);
+        ExtAction::Continue
+    })
+}
+
+// BRK: pause execution and print where we are
+pub fn breakpoint() -> Handler {
+    Box::new(|comp, _params| {
+        eprintln!("Breakpoint at ip {}", comp.inst_pointer);
+        if let Some(bt) = comp.backtrace() {
+            eprint!("{}", bt);
+        }
+        ExtAction::Pause
+    })
+}
+
+// SYS n dest: ask the host for value n and store the result at dest
+pub fn syscall(host: impl Fn(i64) -> i64 + 'static) -> Handler {
+    Box::new(move |comp, params| {
+        let call = params[0].get_value(comp);
+        let idx = params[1].get_idx(comp);
+        comp.write(idx, host(call));
+        ExtAction::Continue
+    })
+}
+
+// JMP target: jump without a condition
+pub fn jump() -> Handler {
+    Box::new(|comp, params| {
+        let target = params[0].get_value(comp);
+        ExtAction::Jump(comp.address(Some(target)))
+    })
+}
+
+// ASSERT x: halt with a message if x is 0
+pub fn assert() -> Handler {
+    Box::new(|comp, params| {
+        if params[0].get_value(comp) != 0 {
+            return ExtAction::Continue;
+        }
+        eprintln!("Assertion failed at ip {}", comp.inst_pointer);
+        if let Some(bt) = comp.backtrace() {
+            eprint!("{}", bt);
+        }
+        ExtAction::Halt
+    })
+}
+
+// a small set of host calls for trying the syscall extension out
+pub fn default_host(call: i64) -> i64 {
+    match call {
+        0 => SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(-1),
+        _ => -1,
+    }
+}
+
+pub fn register_defaults(registry: &mut OpcodeRegistry) {
+    registry.register(20, 1, debug_print()).unwrap(); // DBG
+    registry.register(21, 0, breakpoint()).unwrap(); // BRK
+    registry.register(22, 2, syscall(default_host)).unwrap(); // SYS
+    registry.register(23, 1, jump()).unwrap(); // JMP
+    registry.register(24, 1, assert()).unwrap(); // ASSERT
+}
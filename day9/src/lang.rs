@@ -0,0 +1,131 @@
+// A small structured language that compiles to intcode, for writing test programs.
+//
+//   var total = 0;              // globals live in the image, read in position mode
+//   fn square(x) { return x * x; }
+//   fn main() {
+//       var n = input();        // locals and arguments live on the stack, in relative mode
+//       while n > 0 {
+//           total = total + square(n);
+//           n = n - 1;
+//       }
+//       output(total);          // literals are immediates
+//   }
+//
+// Values are integers, comparisons and ! give 0 or 1, && and || short circuit.
+// Division isn't supported, intcode has no instruction for it.
+
+mod emit;
+mod lexer;
+mod parser;
+
+use std::fmt;
+
+#[derive(Debug)]
+pub struct CompileError {
+    // 1 based, 0 when the error is not tied to a position in the source
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl CompileError {
+    fn new(line: usize, column: usize, message: &str) -> Self {
+        CompileError { line, column, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}", self.message);
+        }
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.column)
+    }
+}
+
+pub fn compile(source: &str) -> Result<Vec<i64>, CompileError> {
+    let tokens = lexer::tokenize(source)?;
+    let program = parser::Parser::new(tokens).program()?;
+    emit::emit(&program)
+}
+
+#[cfg(test)]
+mod tests {
+    use queues::*;
+
+    use super::*;
+    use crate::Computer;
+
+    fn outputs(source: &str, inputs: &[i64]) -> Vec<i64> {
+        let mut comp = Computer::from_memory(compile(source).unwrap());
+        for i in inputs.iter() {
+            comp.input.add(*i).unwrap();
+        }
+        assert!(comp.run());
+        assert_eq!(comp.fault, None);
+        let mut values = Vec::new();
+        while comp.output.size() > 0 {
+            values.push(comp.output.remove().unwrap());
+        }
+        values
+    }
+
+    fn error(source: &str) -> (usize, usize, String) {
+        let e = compile(source).unwrap_err();
+        (e.line, e.column, e.message)
+    }
+
+    #[test]
+    fn arithmetic() {
+        let source = "fn main() { output(2 + 3 * 4); output(-(7 - 10)); output(1 < 2); output(!(3 == 3) || 2 >= 2); }";
+        assert_eq!(outputs(source, &[]), vec![14, 3, 1, 1]);
+    }
+
+    #[test]
+    fn if_and_while() {
+        let source = "
+            var total = 0;
+            fn main() {
+                var i = 0;
+                while i < 10 {
+                    if i == 3 { total = total + 100; } else if i > 7 { total = total - 1; } else { total = total + i; }
+                    i = i + 1;
+                }
+                output(total);
+            }";
+        assert_eq!(outputs(source, &[]), vec![1 + 2 + 100 + 4 + 5 + 6 + 7 - 1 - 1]);
+    }
+
+    #[test]
+    fn recursion() {
+        // every call gets its own frame off the relative base
+        let source = "
+            fn fib(n) { if n < 2 { return n; } return fib(n - 1) + fib(n - 2); }
+            fn fact(n) { if n == 0 { return 1; } return n * fact(n - 1); }
+            fn main() { output(fib(15)); output(fact(10)); }";
+        assert_eq!(outputs(source, &[]), vec![610, 3628800]);
+    }
+
+    #[test]
+    fn input_and_output() {
+        let source = "
+            fn square(x) { return x * x; }
+            fn main() {
+                var n = input();
+                while n > 0 { output(square(input())); n = n - 1; }
+            }";
+        assert_eq!(outputs(source, &[3, 4, -5, 6]), vec![16, 25, 36]);
+    }
+
+    #[test]
+    fn emitter_errors_have_positions() {
+        assert_eq!(error("fn main() {\n    output(x);\n}"), (2, 12, String::from("unknown variable 'x'")));
+        assert_eq!(error("fn main() {\n  y = 1;\n}"), (2, 3, String::from("unknown variable 'y'")));
+        assert_eq!(error("fn main() { f(1); }\nfn f() {}"), (1, 13, String::from("wrong number of arguments to 'f'")));
+        assert_eq!(error("fn main() { g(); }"), (1, 13, String::from("unknown function 'g'")));
+        assert_eq!(error("fn main() {}\nfn main() {}"), (2, 4, String::from("function 'main' is defined twice")));
+        assert_eq!(error("fn main(a) {}"), (1, 4, String::from("main can't take arguments")));
+        assert_eq!(error("var a;\nvar a;\nfn main() {}"), (2, 5, String::from("global 'a' is defined twice")));
+        assert_eq!(error("fn f() {}"), (0, 0, String::from("no main function")));
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use super::parser::BinOp;
+use super::parser::Expr;
+use super::parser::Function;
+use super::parser::Pos;
+use super::parser::Program;
+use super::parser::Stmt;
+use super::CompileError;
+
+// Frame layout, relative to the relative base while a function runs:
+//   [rb+0]           return address
+//   [rb+1..=params]  arguments, the return value is passed back in [rb+1]
+//   then one slot per local, then temporaries for expression evaluation
+// A call at temporary depth d builds the callee's frame starting at the caller's slot
+// temp_base + d, so everything above that is free for the callee to use.
+
+// where an operand lives, which also decides its parameter mode
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Imm(i64),
+    // the address of a label, as an immediate
+    Label(String),
+    // a global, in position mode
+    Global(String),
+    // a slot in the current frame, in relative mode
+    Rel(i64),
+}
+
+impl Operand {
+    fn mode(&self) -> i64 {
+        match self {
+            Operand::Global(_) => 0,
+            Operand::Imm(_) | Operand::Label(_) => 1,
+            Operand::Rel(_) => 2,
+        }
+    }
+}
+
+struct Frame {
+    scopes: Vec<Vec<(String, i64)>>,
+    next_slot: i64,
+    temp_base: i64,
+}
+
+struct Emitter<'a> {
+    code: Vec<i64>,
+    // cells that hold the address of a label, filled in once everything is placed
+    fixups: Vec<(usize, String)>,
+    labels: HashMap<String, usize>,
+    label_count: usize,
+    globals: &'a [(String, i64, Pos)],
+    functions: HashMap<&'a str, usize>,
+}
+
+fn count_locals(stmts: &[Stmt]) -> i64 {
+    stmts.iter().map(|s| match s {
+        Stmt::Var(_, _) => 1,
+        Stmt::If(_, then, otherwise) => count_locals(then) + count_locals(otherwise),
+        Stmt::While(_, body) => count_locals(body),
+        _ => 0,
+    }).sum()
+}
+
+fn error(pos: Pos, message: String) -> CompileError {
+    CompileError::new(pos.line, pos.column, &message)
+}
+
+impl<'a> Emitter<'a> {
+    fn new_label(&mut self) -> String {
+        self.label_count += 1;
+        format!("L{}", self.label_count)
+    }
+
+    fn place(&mut self, label: &str) {
+        self.labels.insert(label.to_string(), self.code.len());
+    }
+
+    fn emit(&mut self, opcode: i64, operands: &[Operand]) {
+        let mut icode = opcode;
+        for (i, op) in operands.iter().enumerate() {
+            icode += op.mode() * 100 * 10i64.pow(i as u32);
+        }
+        self.code.push(icode);
+        for op in operands {
+            match op {
+                Operand::Imm(v) | Operand::Rel(v) => self.code.push(*v),
+                Operand::Label(l) => {
+                    self.fixups.push((self.code.len(), l.clone()));
+                    self.code.push(0);
+                },
+                Operand::Global(g) => {
+                    self.fixups.push((self.code.len(), format!("global:{}", g)));
+                    self.code.push(0);
+                },
+            }
+        }
+    }
+
+    fn store(&mut self, value: Operand, dest: Operand) {
+        if value != dest {
+            self.emit(1, &[value, Operand::Imm(0), dest]);
+        }
+    }
+
+    fn jump(&mut self, target: Operand) {
+        self.emit(5, &[Operand::Imm(1), target]);
+    }
+
+    fn lookup(&self, frame: &Frame, name: &str, pos: Pos) -> Result<Operand, CompileError> {
+        for scope in frame.scopes.iter().rev() {
+            if let Some((_, slot)) = scope.iter().rev().find(|(n, _)| n == name) {
+                return Ok(Operand::Rel(*slot));
+            }
+        }
+        if self.globals.iter().any(|(n, _, _)| n == name) {
+            return Ok(Operand::Global(name.to_string()));
+        }
+        Err(error(pos, format!("unknown variable '{}'", name)))
+    }
+
+    fn program(&mut self, program: &'a Program) -> Result<(), CompileError> {
+        for f in program.functions.iter() {
+            if f.name == "input" || f.name == "output" {
+                return Err(error(f.pos, format!("'{}' is a builtin", f.name)));
+            }
+            if self.functions.insert(&f.name, f.params.len()).is_some() {
+                return Err(error(f.pos, format!("function '{}' is defined twice", f.name)));
+            }
+        }
+        match program.functions.iter().find(|f| f.name == "main") {
+            Some(f) if !f.params.is_empty() => return Err(error(f.pos, "main can't take arguments".to_string())),
+            Some(_) => (),
+            None => return Err(CompileError::new(0, 0, "no main function")),
+        }
+
+        // the stack starts right after the image, main is called like any other function
+        self.emit(9, &[Operand::Label("stack".to_string())]);
+        let ret = self.new_label();
+        self.store(Operand::Label(ret.clone()), Operand::Rel(0));
+        self.jump(Operand::Label("fn:main".to_string()));
+        self.place(&ret);
+        self.emit(99, &[]);
+
+        for f in program.functions.iter() {
+            self.function(f)?;
+        }
+
+        for (name, value, pos) in self.globals.iter() {
+            if self.labels.insert(format!("global:{}", name), self.code.len()).is_some() {
+                return Err(error(*pos, format!("global '{}' is defined twice", name)));
+            }
+            self.code.push(*value);
+        }
+        self.place("stack");
+        for (cell, label) in self.fixups.iter() {
+            self.code[*cell] = self.labels[label] as i64;
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, f: &Function) -> Result<(), CompileError> {
+        self.place(&format!("fn:{}", f.name));
+        let params: Vec<(String, i64)> = f.params.iter().enumerate().map(|(i, p)| (p.clone(), i as i64 + 1)).collect();
+        let next_slot = params.len() as i64 + 1;
+        let mut frame = Frame {
+            scopes: vec![params],
+            next_slot,
+            temp_base: next_slot + count_locals(&f.body),
+        };
+        self.block(&mut frame, &f.body)?;
+        // falling off the end returns 0
+        self.ret(Operand::Imm(0));
+        Ok(())
+    }
+
+    fn ret(&mut self, value: Operand) {
+        self.store(value, Operand::Rel(1));
+        self.jump(Operand::Rel(0));
+    }
+
+    fn block(&mut self, frame: &mut Frame, stmts: &[Stmt]) -> Result<(), CompileError> {
+        frame.scopes.push(Vec::new());
+        for stmt in stmts {
+            self.statement(frame, stmt)?;
+        }
+        frame.scopes.pop();
+        Ok(())
+    }
+
+    fn statement(&mut self, frame: &mut Frame, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Var(name, value) => {
+                // the initialiser can't see the variable it declares
+                let value = self.expr(frame, value, 0)?;
+                let slot = frame.next_slot;
+                frame.next_slot += 1;
+                self.store(value, Operand::Rel(slot));
+                frame.scopes.last_mut().unwrap().push((name.clone(), slot));
+            },
+            Stmt::Assign(name, value, pos) => {
+                let dest = self.lookup(frame, name, *pos)?;
+                let value = self.expr(frame, value, 0)?;
+                self.store(value, dest);
+            },
+            Stmt::If(cond, then, otherwise) => {
+                let else_label = self.new_label();
+                let end = self.new_label();
+                let cond = self.expr(frame, cond, 0)?;
+                self.emit(6, &[cond, Operand::Label(else_label.clone())]);
+                self.block(frame, then)?;
+                self.jump(Operand::Label(end.clone()));
+                self.place(&else_label);
+                self.block(frame, otherwise)?;
+                self.place(&end);
+            },
+            Stmt::While(cond, body) => {
+                let top = self.new_label();
+                let end = self.new_label();
+                self.place(&top);
+                let cond = self.expr(frame, cond, 0)?;
+                self.emit(6, &[cond, Operand::Label(end.clone())]);
+                self.block(frame, body)?;
+                self.jump(Operand::Label(top));
+                self.place(&end);
+            },
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expr(frame, value, 0)?,
+                    None => Operand::Imm(0),
+                };
+                self.ret(value);
+            },
+            Stmt::Expr(expr) => {
+                self.expr(frame, expr, 0)?;
+            },
+        }
+        Ok(())
+    }
+
+    // Evaluates an expression, any temporaries it needs are at depth and above.
+    // The result is either an operand that needs no code or the temporary at depth.
+    fn expr(&mut self, frame: &Frame, expr: &Expr, depth: i64) -> Result<Operand, CompileError> {
+        let slot = Operand::Rel(frame.temp_base + depth);
+        match expr {
+            Expr::Int(v) => Ok(Operand::Imm(*v)),
+            Expr::Var(name, pos) => self.lookup(frame, name, *pos),
+            Expr::Neg(inner) => {
+                let inner = self.expr(frame, inner, depth)?;
+                if let Operand::Imm(v) = inner {
+                    return Ok(Operand::Imm(-v));
+                }
+                self.emit(2, &[inner, Operand::Imm(-1), slot.clone()]);
+                Ok(slot)
+            },
+            Expr::Not(inner) => {
+                let inner = self.expr(frame, inner, depth)?;
+                self.emit(8, &[inner, Operand::Imm(0), slot.clone()]);
+                Ok(slot)
+            },
+            Expr::Binary(op @ BinOp::And, lhs, rhs) | Expr::Binary(op @ BinOp::Or, lhs, rhs) => {
+                // short circuit, the result is always 0 or 1
+                let short = self.new_label();
+                let end = self.new_label();
+                let lhs = self.expr(frame, lhs, depth)?;
+                self.emit(if *op == BinOp::And { 6 } else { 5 }, &[lhs, Operand::Label(short.clone())]);
+                let rhs = self.expr(frame, rhs, depth)?;
+                self.emit(8, &[rhs, Operand::Imm(0), slot.clone()]);
+                self.emit(8, &[slot.clone(), Operand::Imm(0), slot.clone()]);
+                self.jump(Operand::Label(end.clone()));
+                self.place(&short);
+                self.store(Operand::Imm((*op == BinOp::Or) as i64), slot.clone());
+                self.place(&end);
+                Ok(slot)
+            },
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.expr(frame, lhs, depth)?;
+                let rhs = self.expr(frame, rhs, depth + 1)?;
+                match *op {
+                    BinOp::Add => self.emit(1, &[lhs, rhs, slot.clone()]),
+                    BinOp::Sub => match rhs {
+                        Operand::Imm(v) => self.emit(1, &[lhs, Operand::Imm(-v), slot.clone()]),
+                        rhs => {
+                            let negated = Operand::Rel(frame.temp_base + depth + 1);
+                            self.emit(2, &[rhs, Operand::Imm(-1), negated.clone()]);
+                            self.emit(1, &[lhs, negated, slot.clone()]);
+                        },
+                    },
+                    BinOp::Mul => self.emit(2, &[lhs, rhs, slot.clone()]),
+                    BinOp::Lt => self.emit(7, &[lhs, rhs, slot.clone()]),
+                    BinOp::Gt => self.emit(7, &[rhs, lhs, slot.clone()]),
+                    BinOp::Eq => self.emit(8, &[lhs, rhs, slot.clone()]),
+                    // the rest are the negation of one of the above
+                    BinOp::Le | BinOp::Ge | BinOp::Ne => {
+                        match *op {
+                            BinOp::Le => self.emit(7, &[rhs, lhs, slot.clone()]),
+                            BinOp::Ge => self.emit(7, &[lhs, rhs, slot.clone()]),
+                            _ => self.emit(8, &[lhs, rhs, slot.clone()]),
+                        }
+                        self.emit(8, &[slot.clone(), Operand::Imm(0), slot.clone()]);
+                    },
+                    BinOp::And | BinOp::Or => unreachable!(),
+                }
+                Ok(slot)
+            },
+            Expr::Call(name, args, pos) => self.call(frame, name, args, *pos, depth),
+        }
+    }
+
+    fn call(&mut self, frame: &Frame, name: &str, args: &[Expr], pos: Pos, depth: i64) -> Result<Operand, CompileError> {
+        let slot = Operand::Rel(frame.temp_base + depth);
+        match (name, args.len()) {
+            ("input", 0) => {
+                self.emit(3, std::slice::from_ref(&slot));
+                return Ok(slot);
+            },
+            ("output", 1) => {
+                let value = self.expr(frame, &args[0], depth)?;
+                self.emit(4, &[value]);
+                return Ok(Operand::Imm(0));
+            },
+            ("input", _) | ("output", _) => return Err(error(pos, format!("wrong number of arguments to '{}'", name))),
+            _ => (),
+        }
+        match self.functions.get(name) {
+            Some(n) if *n == args.len() => (),
+            Some(_) => return Err(error(pos, format!("wrong number of arguments to '{}'", name))),
+            None => return Err(error(pos, format!("unknown function '{}'", name))),
+        }
+
+        let base = frame.temp_base + depth;
+        for (i, arg) in args.iter().enumerate() {
+            let value = self.expr(frame, arg, depth + 1 + i as i64)?;
+            self.store(value, Operand::Rel(base + 1 + i as i64));
+        }
+        let ret = self.new_label();
+        self.store(Operand::Label(ret.clone()), Operand::Rel(base));
+        self.emit(9, &[Operand::Imm(base)]);
+        self.jump(Operand::Label(format!("fn:{}", name)));
+        self.place(&ret);
+        self.emit(9, &[Operand::Imm(-base)]);
+        self.store(Operand::Rel(base + 1), slot.clone());
+        Ok(slot)
+    }
+}
+
+pub fn emit(program: &Program) -> Result<Vec<i64>, CompileError> {
+    let mut emitter = Emitter {
+        code: Vec::new(),
+        fixups: Vec::new(),
+        labels: HashMap::new(),
+        label_count: 0,
+        globals: &program.globals,
+        functions: HashMap::new(),
+    };
+    emitter.program(program)?;
+    Ok(emitter.code)
+}
@@ -0,0 +1,105 @@
+use super::CompileError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Int(i64),
+    Ident(String),
+    Fn,
+    Var,
+    If,
+    Else,
+    While,
+    Return,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Semi,
+    Assign,
+    Plus,
+    Minus,
+    Star,
+    Not,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+pub struct Spanned {
+    pub token: Token,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Spanned>, CompileError> {
+    let mut tokens = Vec::new();
+    for (line_no, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let column = i + 1;
+            let next = chars.get(i + 1).copied();
+            if c.is_whitespace() {
+                i += 1;
+                continue;
+            }
+            // comments run to the end of the line
+            if c == '/' && next == Some('/') {
+                break;
+            }
+            let (token, len) = if c.is_ascii_digit() {
+                let len = chars[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let text: String = chars[i..i + len].iter().collect();
+                let value = text.parse().map_err(|_| CompileError::new(line_no + 1, column, "integer literal too large"))?;
+                (Token::Int(value), len)
+            } else if c.is_alphabetic() || c == '_' {
+                let len = chars[i..].iter().take_while(|c| c.is_alphanumeric() || **c == '_').count();
+                let text: String = chars[i..i + len].iter().collect();
+                let token = match text.as_str() {
+                    "fn" => Token::Fn,
+                    "var" => Token::Var,
+                    "if" => Token::If,
+                    "else" => Token::Else,
+                    "while" => Token::While,
+                    "return" => Token::Return,
+                    _ => Token::Ident(text),
+                };
+                (token, len)
+            } else {
+                match (c, next) {
+                    ('<', Some('=')) => (Token::Le, 2),
+                    ('>', Some('=')) => (Token::Ge, 2),
+                    ('=', Some('=')) => (Token::Eq, 2),
+                    ('!', Some('=')) => (Token::Ne, 2),
+                    ('&', Some('&')) => (Token::And, 2),
+                    ('|', Some('|')) => (Token::Or, 2),
+                    ('(', _) => (Token::LParen, 1),
+                    (')', _) => (Token::RParen, 1),
+                    ('{', _) => (Token::LBrace, 1),
+                    ('}', _) => (Token::RBrace, 1),
+                    (',', _) => (Token::Comma, 1),
+                    (';', _) => (Token::Semi, 1),
+                    ('=', _) => (Token::Assign, 1),
+                    ('+', _) => (Token::Plus, 1),
+                    ('-', _) => (Token::Minus, 1),
+                    ('*', _) => (Token::Star, 1),
+                    ('!', _) => (Token::Not, 1),
+                    ('<', _) => (Token::Lt, 1),
+                    ('>', _) => (Token::Gt, 1),
+                    _ => return Err(CompileError::new(line_no + 1, column, &format!("unexpected character '{}'", c))),
+                }
+            };
+            tokens.push(Spanned { token, line: line_no + 1, column });
+            i += len;
+        }
+    }
+    Ok(tokens)
+}
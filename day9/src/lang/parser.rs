@@ -0,0 +1,290 @@
+use super::lexer::Spanned;
+use super::lexer::Token;
+use super::CompileError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+// where something starts in the source, 1 based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Int(i64),
+    Var(String, Pos),
+    Call(String, Vec<Expr>, Pos),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Var(String, Expr),
+    Assign(String, Expr, Pos),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub pos: Pos,
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Default)]
+pub struct Program {
+    pub globals: Vec<(String, i64, Pos)>,
+    pub functions: Vec<Function>,
+}
+
+pub struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Spanned>) -> Self {
+        Parser { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    // the position of the next token, or of the last one at the end of the source
+    fn here(&self) -> Pos {
+        match self.tokens.get(self.pos).or_else(|| self.tokens.last()) {
+            Some(t) => Pos { line: t.line, column: t.column },
+            None => Pos { line: 1, column: 1 },
+        }
+    }
+
+    fn error(&self, message: &str) -> CompileError {
+        let pos = self.here();
+        CompileError::new(pos.line, pos.column, message)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|t| t.token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), CompileError> {
+        if self.eat(&token) {
+            return Ok(());
+        }
+        Err(self.error(&format!("expected {:?}", token)))
+    }
+
+    fn ident(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            },
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    pub fn program(&mut self) -> Result<Program, CompileError> {
+        let mut program = Program::default();
+        while self.peek().is_some() {
+            if self.eat(&Token::Var) {
+                // globals are initialised with a constant, they live in the program image
+                let pos = self.here();
+                let name = self.ident()?;
+                let mut value = 0;
+                if self.eat(&Token::Assign) {
+                    let negative = self.eat(&Token::Minus);
+                    value = match self.next() {
+                        Some(Token::Int(v)) => if negative { -v } else { v },
+                        _ => return Err(self.error("globals must be initialised with an integer")),
+                    };
+                }
+                self.expect(Token::Semi)?;
+                program.globals.push((name, value, pos));
+            } else if self.eat(&Token::Fn) {
+                let pos = self.here();
+                let name = self.ident()?;
+                self.expect(Token::LParen)?;
+                let mut params = Vec::new();
+                if !self.eat(&Token::RParen) {
+                    loop {
+                        params.push(self.ident()?);
+                        if self.eat(&Token::RParen) {
+                            break;
+                        }
+                        self.expect(Token::Comma)?;
+                    }
+                }
+                let body = self.block()?;
+                program.functions.push(Function { name, pos, params, body });
+            } else {
+                return Err(self.error("expected fn or var"));
+            }
+        }
+        Ok(program)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect(Token::LBrace)?;
+        let mut stmts = Vec::new();
+        while !self.eat(&Token::RBrace) {
+            if self.peek().is_none() {
+                return Err(self.error("unclosed block"));
+            }
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        if self.eat(&Token::Var) {
+            let name = self.ident()?;
+            self.expect(Token::Assign)?;
+            let value = self.expr()?;
+            self.expect(Token::Semi)?;
+            return Ok(Stmt::Var(name, value));
+        }
+        if self.eat(&Token::If) {
+            return self.if_rest();
+        }
+        if self.eat(&Token::While) {
+            let cond = self.expr()?;
+            let body = self.block()?;
+            return Ok(Stmt::While(cond, body));
+        }
+        if self.eat(&Token::Return) {
+            if self.eat(&Token::Semi) {
+                return Ok(Stmt::Return(None));
+            }
+            let value = self.expr()?;
+            self.expect(Token::Semi)?;
+            return Ok(Stmt::Return(Some(value)));
+        }
+        // assignment needs a second token of lookahead
+        if let (Some(Token::Ident(name)), Some(Token::Assign)) = (self.peek().cloned(), self.tokens.get(self.pos + 1).map(|t| &t.token)) {
+            let pos = self.here();
+            self.pos += 2;
+            let value = self.expr()?;
+            self.expect(Token::Semi)?;
+            return Ok(Stmt::Assign(name, value, pos));
+        }
+        let expr = self.expr()?;
+        self.expect(Token::Semi)?;
+        Ok(Stmt::Expr(expr))
+    }
+
+    fn if_rest(&mut self) -> Result<Stmt, CompileError> {
+        let cond = self.expr()?;
+        let then = self.block()?;
+        let mut otherwise = Vec::new();
+        if self.eat(&Token::Else) {
+            if self.eat(&Token::If) {
+                otherwise.push(self.if_rest()?);
+            } else {
+                otherwise = self.block()?;
+            }
+        }
+        Ok(Stmt::If(cond, then, otherwise))
+    }
+
+    pub fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    // precedence climbing, loosest binding first
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        const LEVELS: [&[(Token, BinOp)]; 5] = [
+            &[(Token::Or, BinOp::Or)],
+            &[(Token::And, BinOp::And)],
+            &[(Token::Lt, BinOp::Lt), (Token::Gt, BinOp::Gt), (Token::Le, BinOp::Le),
+              (Token::Ge, BinOp::Ge), (Token::Eq, BinOp::Eq), (Token::Ne, BinOp::Ne)],
+            &[(Token::Plus, BinOp::Add), (Token::Minus, BinOp::Sub)],
+            &[(Token::Star, BinOp::Mul)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for (token, op) in LEVELS[level].iter() {
+                if self.eat(token) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let pos = self.here();
+        match self.next() {
+            Some(Token::Int(v)) => Ok(Expr::Int(v)),
+            Some(Token::Ident(name)) => {
+                if !self.eat(&Token::LParen) {
+                    return Ok(Expr::Var(name, pos));
+                }
+                let mut args = Vec::new();
+                if !self.eat(&Token::RParen) {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(&Token::RParen) {
+                            break;
+                        }
+                        self.expect(Token::Comma)?;
+                    }
+                }
+                Ok(Expr::Call(name, args, pos))
+            },
+            Some(Token::LParen) => {
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            },
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected an expression"))
+            },
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::fs;
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+
+// every gzip stream starts with these two bytes
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug)]
+pub enum LoadErrorKind {
+    Io(String),
+    Decompress(String),
+    NotUtf8,
+    BadToken(String),
+    Empty,
+}
+
+#[derive(Debug)]
+pub struct LoadError {
+    pub kind: LoadErrorKind,
+    // 1 based, 0 when the error is not tied to a position in the source
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LoadError {
+    fn new(kind: LoadErrorKind) -> Self {
+        LoadError { kind, line: 0, column: 0 }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            LoadErrorKind::Io(e) => write!(f, "unable to read program: {}", e),
+            LoadErrorKind::Decompress(e) => write!(f, "unable to decompress program: {}", e),
+            LoadErrorKind::NotUtf8 => write!(f, "program is not valid UTF-8"),
+            LoadErrorKind::BadToken(t) => write!(f, "invalid value '{}' at line {}, column {}", t, self.line, self.column),
+            LoadErrorKind::Empty => write!(f, "program is empty"),
+        }
+    }
+}
+
+// values may be separated by commas, whitespace or newlines, and '#' starts a comment
+pub fn load_str(source: &str) -> Result<Vec<i64>, LoadError> {
+    let mut program = Vec::new();
+    for (line_no, line) in source.lines().enumerate() {
+        let code = match line.find('#') {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        let mut start: Option<usize> = None;
+        // walk one past the end so the last token on the line is flushed
+        for (i, c) in code.char_indices().chain(std::iter::once((code.len(), ','))) {
+            let is_sep = c == ',' || c.is_whitespace();
+            match (start, is_sep) {
+                (None, false) => start = Some(i),
+                (Some(s), true) => {
+                    let token = &code[s..i];
+                    let value = token.parse::<i64>().map_err(|_| LoadError {
+                        kind: LoadErrorKind::BadToken(token.to_string()),
+                        line: line_no + 1,
+                        column: code[..s].chars().count() + 1,
+                    })?;
+                    program.push(value);
+                    start = None;
+                },
+                _ => (),
+            }
+        }
+    }
+    if program.is_empty() {
+        return Err(LoadError::new(LoadErrorKind::Empty));
+    }
+    Ok(program)
+}
+
+// accepts either plain text or a gzip compressed image
+pub fn load_bytes(bytes: &[u8]) -> Result<Vec<i64>, LoadError> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut text = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut text)
+            .map_err(|e| LoadError::new(LoadErrorKind::Decompress(e.to_string())))?;
+        return load_str(&text);
+    }
+    let text = std::str::from_utf8(bytes).map_err(|_| LoadError::new(LoadErrorKind::NotUtf8))?;
+    load_str(text)
+}
+
+pub fn load_file(file_path: &str) -> Result<Vec<i64>, LoadError> {
+    let bytes = fs::read(file_path).map_err(|e| LoadError::new(LoadErrorKind::Io(e.to_string())))?;
+    load_bytes(&bytes)
+}
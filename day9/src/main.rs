@@ -1,8 +1,39 @@
 extern crate queues;
 
+mod callstack;
+mod codegen;
+mod disasm;
+mod extensions;
+mod lang;
+mod loader;
+mod optimizer;
+mod patch;
+mod profiler;
+mod search;
+mod symbolic;
+
+use std::env;
 use std::fs;
+use std::io::Read;
+use std::io::Write;
+use std::thread;
+use std::time::Instant;
 use queues::*;
+use callstack::CallStack;
+use extensions::ExtAction;
+use extensions::OpcodeRegistry;
+use patch::Patch;
+use profiler::Profiler;
+use search::Goal;
+use search::PatchRange;
+use search::Search;
+use symbolic::SymbolicComputer;
+use symbolic::VarRange;
+
+// far more than any puzzle program needs, but small enough that a runaway address can't exhaust memory
+const DEFAULT_MAX_MEMORY: usize = 1 << 24;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 enum Opcode {
     ADD,
     MUL,
@@ -14,26 +45,28 @@ enum Opcode {
     EQ,
     ARB,
     HALT,
+    EXT(i64),
 }
 
 impl Opcode {
-    fn from_i64(i: i64) -> Self {
+    fn from_i64(i: i64, registry: &OpcodeRegistry) -> Result<Self, String> {
         match i {
-            1 => Opcode::ADD,
-            2 => Opcode::MUL,
-            3 => Opcode::INP,
-            4 => Opcode::OUT,
-            5 => Opcode::JIT,
-            6 => Opcode::JIF,
-            7 => Opcode::LT,
-            8 => Opcode::EQ,
-            9 => Opcode::ARB,
-            99 => Opcode::HALT,
-            _ => panic!("Unknown opcode {}", i),
+            1 => Ok(Opcode::ADD),
+            2 => Ok(Opcode::MUL),
+            3 => Ok(Opcode::INP),
+            4 => Ok(Opcode::OUT),
+            5 => Ok(Opcode::JIT),
+            6 => Ok(Opcode::JIF),
+            7 => Ok(Opcode::LT),
+            8 => Ok(Opcode::EQ),
+            9 => Ok(Opcode::ARB),
+            99 => Ok(Opcode::HALT),
+            _ if registry.get(i).is_some() => Ok(Opcode::EXT(i)),
+            _ => Err(format!("Unknown opcode {}", i)),
         }
     }
 
-    fn param_count(&self) -> u32 {
+    fn param_count(&self, registry: &OpcodeRegistry) -> u32 {
         match self {
             Opcode::ADD => 3,
             Opcode::MUL => 3,
@@ -45,10 +78,12 @@ impl Opcode {
             Opcode::EQ => 3,
             Opcode::ARB => 1,
             Opcode::HALT => 0,
+            Opcode::EXT(i) => registry.get(*i).unwrap().param_count,
         }
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 enum ParamMode {
     POSITION,
     IMMEDIATE,
@@ -56,12 +91,12 @@ enum ParamMode {
 }
 
 impl ParamMode {
-    fn from_i64(i: i64) -> Self {
+    fn from_i64(i: i64) -> Result<Self, String> {
         match i {
-            0 => ParamMode::POSITION,
-            1 => ParamMode::IMMEDIATE,
-            2 => ParamMode::RELATIVE,
-            _ => panic!("Unknown opcode {}", i),
+            0 => Ok(ParamMode::POSITION),
+            1 => Ok(ParamMode::IMMEDIATE),
+            2 => Ok(ParamMode::RELATIVE),
+            _ => Err(format!("Unknown parameter mode {}", i)),
         }
     }
 }
@@ -74,17 +109,23 @@ struct Parameter {
 impl Parameter {
     fn get_value(&self, comp: &mut Computer) -> i64 {
         match self.mode {
-            ParamMode::POSITION => comp.read(self.value as usize),
             ParamMode::IMMEDIATE => self.value,
-            ParamMode::RELATIVE => comp.read((self.value + (comp.relative_base as i64)) as usize),
+            _ => {
+                let addr = self.get_idx(comp);
+                comp.read_data(addr)
+            },
         }
     }
 
+    // the address the parameter refers to, a bad address faults the computer and gives 0
     fn get_idx(&self, comp: &mut Computer) -> usize {
         match self.mode {
-            ParamMode::POSITION => self.value as usize,
-            ParamMode::IMMEDIATE => panic!("Index should never be in immidiate mode"),
-            ParamMode::RELATIVE => (self.value + (comp.relative_base as i64)) as usize,
+            ParamMode::POSITION => comp.address(Some(self.value)),
+            ParamMode::IMMEDIATE => {
+                comp.set_fault(String::from("Write target in immediate mode"));
+                0
+            },
+            ParamMode::RELATIVE => comp.address(self.value.checked_add(comp.relative_base as i64)),
         }
     }
 }
@@ -99,22 +140,22 @@ impl Instruction {
         return self.parameters.len() + 1;
     }
 
-    fn new(icode: i64, comp: &mut Computer) -> Self {
+    fn new(icode: i64, comp: &mut Computer) -> Result<Self, String> {
         // parse the opcode from the instruction code
-        let opcode = Opcode::from_i64(icode % 100);
+        let opcode = Opcode::from_i64(icode % 100, &comp.extensions)?;
         // get the param modes and values for each param in the instruction
-        let param_count = opcode.param_count();
+        let param_count = opcode.param_count(&comp.extensions);
         let mut params = Vec::new();
         for i in 0..param_count {
             params.push(Parameter {
-                mode: ParamMode::from_i64((icode / (100 * 10i64.pow(i))) % 10),
+                mode: ParamMode::from_i64((icode / (100 * 10i64.pow(i))) % 10)?,
                 value: comp.read(comp.inst_pointer + 1 + i as usize),
             });
         }
-        return Instruction {
-            opcode: opcode,
+        Ok(Instruction {
+            opcode,
             parameters: params,
-        };
+        })
     }
 }
 
@@ -124,96 +165,231 @@ struct Computer {
     output: Queue<i64>,
     inst_pointer: usize,
     relative_base: usize,
+    profiler: Option<Profiler>,
+    call_stack: Option<CallStack>,
+    extensions: OpcodeRegistry,
+    // print every instruction to stderr as it runs
+    trace: bool,
+    steps: u64,
+    max_steps: Option<u64>,
+    // memory can't grow to this many cells or more
+    max_memory: usize,
+    // why run stopped early, on an instruction it couldn't decode or a bad address or value
+    fault: Option<String>,
 }
 
 impl Computer {
     fn new(file_path: &str) -> Self {
+        match loader::load_file(file_path) {
+            Ok(memory) => Computer::from_memory(memory),
+            Err(e) => panic!("{}: {}", file_path, e),
+        }
+    }
+
+    fn from_memory(memory: Vec<i64>) -> Self {
         Computer {
-            memory: fs::read_to_string(file_path)
-                    .expect("Unable to read file")
-                    .lines()
-                    .next()
-                    .expect("Invalid input")
-                    .split(",")
-                    .map(|x| x.parse::<i64>().expect("Unable to parse"))
-                    .collect(),
+            memory,
             input: Queue::new(),
             output: Queue::new(),
             inst_pointer: 0,
             relative_base: 0,
+            profiler: None,
+            call_stack: None,
+            extensions: OpcodeRegistry::new(),
+            trace: false,
+            steps: 0,
+            max_steps: None,
+            max_memory: DEFAULT_MAX_MEMORY,
+            fault: None,
         }
     }
 
-    fn read(&mut self, addr: usize) -> i64 {
-        if self.memory.len() < addr {
-            self.memory.resize(addr + 10, 0);
+    // only the first fault is kept, anything after it is a consequence
+    fn set_fault(&mut self, message: String) {
+        if self.fault.is_none() {
+            self.fault = Some(format!("{} at ip {}", message, self.inst_pointer));
         }
-        return self.memory[addr];
     }
 
-    fn write(&mut self, addr: usize, value: i64) {
+    // checks an address worked out by the program, None if working it out overflowed
+    fn address(&mut self, addr: Option<i64>) -> usize {
+        match addr {
+            Some(addr) if addr >= 0 => addr as usize,
+            Some(addr) => {
+                self.set_fault(format!("Negative address {}", addr));
+                0
+            },
+            None => {
+                self.set_fault(String::from("Address overflowed"));
+                0
+            },
+        }
+    }
+
+    // None if the value overflowed, which faults the computer
+    fn checked(&mut self, value: Option<i64>) -> i64 {
+        value.unwrap_or_else(|| {
+            self.set_fault(String::from("Arithmetic overflow"));
+            0
+        })
+    }
+
+    // grows memory to hold addr, false and a fault if that would pass max_memory
+    fn reserve(&mut self, addr: usize) -> bool {
+        if addr >= self.max_memory {
+            self.set_fault(format!("Address {} is past the memory limit of {}", addr, self.max_memory));
+            return false;
+        }
         if self.memory.len() <= addr {
             self.memory.resize(addr + 10, 0);
         }
+        true
+    }
+
+    // run stops early, as if waiting on input, once this many instructions have run
+    fn out_of_steps(&self) -> bool {
+        matches!(self.max_steps, Some(max) if self.steps >= max)
+    }
+
+    // overrides are applied in order, so a later patch to the same address wins
+    fn apply_patches(&mut self, patches: &[Patch]) {
+        for patch in patches.iter() {
+            self.write(patch.addr, patch.value);
+        }
+    }
+
+    fn enable_profiler(&mut self) {
+        self.profiler = Some(Profiler::new(self.memory.len()));
+    }
+
+    fn enable_call_stack(&mut self) {
+        self.call_stack = Some(CallStack::new());
+    }
+
+    fn backtrace(&self) -> Option<String> {
+        self.call_stack.as_ref().map(|cs| cs.backtrace(self.inst_pointer))
+    }
+
+    // feed a taken jump to the call stack tracker, keeping the profiler's frames in sync
+    fn trace_jump(&mut self, from: usize, next: usize) {
+        if let Some(call_stack) = self.call_stack.as_mut() {
+            if call_stack.record_jump(from, next, self.inst_pointer, self.relative_base) {
+                if let Some(profiler) = self.profiler.as_mut() {
+                    profiler.set_call_frames(call_stack.frames().iter().map(|f| f.entry).collect());
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, addr: usize) -> i64 {
+        if !self.reserve(addr) {
+            return 0;
+        }
+        self.memory[addr]
+    }
+
+    // reads made by an instruction's parameters, as opposed to fetching the instruction itself
+    fn read_data(&mut self, addr: usize) -> i64 {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_read(addr);
+        }
+        self.read(addr)
+    }
+
+    fn write(&mut self, addr: usize, value: i64) {
+        // nothing is written once something has gone wrong
+        if self.fault.is_some() || !self.reserve(addr) {
+            return;
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_write(addr);
+        }
+        if let Some(call_stack) = self.call_stack.as_mut() {
+            call_stack.record_write(value);
+        }
         self.memory[addr] = value;
     }
 
     fn run(&mut self) -> bool {
         while self.inst_pointer < self.memory.len() {
+            if self.fault.is_some() {
+                return true; // the program can't go any further, so it counts as halted
+            }
+            if self.out_of_steps() {
+                return false;
+            }
+            self.steps += 1;
             let icode = self.memory[self.inst_pointer];
-            let inst = Instruction::new(icode, self);
+            let inst = match Instruction::new(icode, self) {
+                Ok(inst) => inst,
+                Err(e) => {
+                    self.set_fault(format!("{} in instruction {}", e, icode));
+                    return true;
+                },
+            };
+            // an INP without input runs again once there is some, so it isn't traced or counted yet
+            if inst.opcode == Opcode::INP && self.input.size() < 1 {
+                self.steps -= 1;
+                return false; // we have not halted but are waiting on input
+            }
+            if self.trace {
+                let traced = disasm::StaticInst {
+                    addr: self.inst_pointer,
+                    opcode: inst.opcode,
+                    modes: inst.parameters.iter().map(|p| p.mode).collect(),
+                    params: inst.parameters.iter().map(|p| p.value).collect(),
+                };
+                eprintln!("{:<40} rb={}", traced.to_string(), self.relative_base);
+            }
+            if let Some(profiler) = self.profiler.as_mut() {
+                let modes = inst.parameters.iter().map(|p| p.mode).collect();
+                profiler.record_inst(self.inst_pointer, inst.opcode, modes);
+            }
             let mut jumped = false;
             match inst.opcode {
                 Opcode::ADD => {
                     let lhs = inst.parameters[0].get_value(self);
                     let rhs = inst.parameters[1].get_value(self);
                     let idx = inst.parameters[2].get_idx(self);
-                    self.write(idx, lhs + rhs);
+                    let sum = self.checked(lhs.checked_add(rhs));
+                    self.write(idx, sum);
                 },
                 Opcode::MUL => {
                     let lhs = inst.parameters[0].get_value(self);
                     let rhs = inst.parameters[1].get_value(self);
                     let idx = inst.parameters[2].get_idx(self);
-                    self.write(idx, lhs * rhs);
+                    let product = self.checked(lhs.checked_mul(rhs));
+                    self.write(idx, product);
                 },
                 Opcode::INP => {
                     let idx = inst.parameters[0].get_idx(self);
-                    if self.input.size() < 1 {
-                        return false; // we have not halted but are waiting on input
-                    }
                     let inp = self.input.remove().unwrap();
                     self.write(idx, inp);
                 },
                 Opcode::OUT => {
-                    match inst.parameters[0].mode {
-                        ParamMode::IMMEDIATE => {
-                            self.output.add(inst.parameters[0].value).unwrap();
-                        },
-                        ParamMode::POSITION => {
-                            let outp = self.read(inst.parameters[0].value as usize);
-                            self.output.add(outp).unwrap();
-                        },
-                        ParamMode::RELATIVE => {
-                            let idx = (self.relative_base as i64) + inst.parameters[0].value;
-                            let outp = self.read(idx as usize);
-                            self.output.add(outp).unwrap();
-                        }
-                    };
+                    let outp = inst.parameters[0].get_value(self);
+                    if self.fault.is_none() {
+                        self.output.add(outp).unwrap();
+                    }
                 },
                 Opcode::JIT => {
                     let test = inst.parameters[0].get_value(self);
-                    let new_ip = inst.parameters[1].get_value(self) as usize;
+                    let new_ip = inst.parameters[1].get_value(self);
                     if test != 0 {
-                        self.inst_pointer = new_ip;
+                        let from = self.inst_pointer;
+                        self.inst_pointer = self.address(Some(new_ip));
                         jumped = true; // make sure we dont increment the instruction pointer after the jump
+                        self.trace_jump(from, from + inst.len());
                     }
                 },
                 Opcode::JIF => {
                     let test = inst.parameters[0].get_value(self);
-                    let new_ip = inst.parameters[1].get_value(self) as usize;
+                    let new_ip = inst.parameters[1].get_value(self);
                     if test == 0 {
-                        self.inst_pointer = new_ip;
+                        let from = self.inst_pointer;
+                        self.inst_pointer = self.address(Some(new_ip));
                         jumped = true; // make sure we dont increment the instruction pointer after the jump
+                        self.trace_jump(from, from + inst.len());
                     }
                 },
                 Opcode::LT => {
@@ -239,15 +415,48 @@ impl Computer {
                 Opcode::ARB =>  {
                     let p0 = inst.parameters[0].get_value(self);
                     let rb = self.relative_base as i64;
-                    if rb < 0 {
-                        panic!("Relative base should not be negative");
+                    let new_rb = match rb.checked_add(p0) {
+                        Some(new_rb) if new_rb >= 0 => new_rb,
+                        _ => {
+                            self.set_fault(format!("Relative base {} can't be moved by {}", rb, p0));
+                            return true;
+                        },
+                    };
+                    // compiled programs grow the relative base on function entry and shrink it on return,
+                    // which stands in for call frames unless the call stack is being reconstructed
+                    if let (Some(profiler), None) = (self.profiler.as_mut(), self.call_stack.as_ref()) {
+                        if p0 > 0 {
+                            profiler.push_frame(self.inst_pointer);
+                        } else if p0 < 0 {
+                            profiler.pop_frame();
+                        }
                     }
-                    self.relative_base = (rb + p0) as usize;
+                    self.relative_base = new_rb as usize;
                 },
                 Opcode::HALT => {
                     return true;
                 },
+                Opcode::EXT(i) => {
+                    let ext = self.extensions.get(i).unwrap();
+                    match ext.call(self, &inst.parameters) {
+                        ExtAction::Continue => (),
+                        ExtAction::Jump(new_ip) => {
+                            let from = self.inst_pointer;
+                            self.inst_pointer = new_ip;
+                            jumped = true;
+                            self.trace_jump(from, from + inst.len());
+                        },
+                        ExtAction::Pause => {
+                            self.inst_pointer += inst.len();
+                            return false; // resumable, same as waiting on input
+                        },
+                        ExtAction::Halt => return true,
+                    }
+                },
             };
+            if self.fault.is_some() {
+                return true;
+            }
             // if we didnt jump we increment the instruction pointer by the len of the instruction
             if !jumped {
                 self.inst_pointer += inst.len();
@@ -257,11 +466,393 @@ impl Computer {
     }
 }
 
+// search <program> --range addr=start..end ... --goal memN=value|out=value [--input n] [--threads n] [--max-steps n] [--max-memory n]
+fn search_main(args: &[String]) {
+    let mut program = String::from("input.txt");
+    let mut ranges: Vec<PatchRange> = Vec::new();
+    let mut inputs: Vec<i64> = Vec::new();
+    let mut goal: Option<Goal> = None;
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut max_steps = search::DEFAULT_MAX_STEPS;
+    let mut max_memory = search::DEFAULT_MAX_MEMORY;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-steps" => max_steps = args.next().and_then(|n| n.parse().ok()).expect("--max-steps needs a number"),
+            "--max-memory" => max_memory = args.next().and_then(|n| n.parse().ok()).expect("--max-memory needs a number of cells"),
+            "--range" => {
+                let spec = args.next().expect("--range needs address=start..end");
+                ranges.push(PatchRange::parse(spec).unwrap_or_else(|e| panic!("{}", e)));
+            },
+            "--goal" => {
+                let spec = args.next().expect("--goal needs memN=value or out=value");
+                goal = Some(Goal::parse(spec).unwrap_or_else(|e| panic!("{}", e)));
+            },
+            "--input" => {
+                let value = args.next().expect("--input needs a value");
+                inputs.push(value.parse().expect("Invalid input value"));
+            },
+            "--threads" => {
+                let value = args.next().expect("--threads needs a value");
+                threads = value.parse().expect("Invalid thread count");
+            },
+            _ => program = arg.clone(),
+        }
+    }
+
+    let memory = loader::load_file(&program).unwrap_or_else(|e| panic!("{}: {}", program, e));
+    let search = Search {
+        program: &memory,
+        ranges: &ranges,
+        inputs: &inputs,
+        goal: goal.expect("A --goal is required"),
+        threads,
+        max_steps,
+        max_memory,
+    };
+    let result = search.run(|tried, total| {
+        eprint!("\rTried {}/{} candidates", tried, total);
+        std::io::stderr().flush().unwrap();
+    });
+    eprintln!();
+    match result {
+        Some(patches) => {
+            let found: Vec<String> = patches.iter().map(|p| format!("{}={}", p.addr, p.value)).collect();
+            println!("Found: {}", found.join(","));
+        },
+        None => println!("No candidate met the goal"),
+    }
+}
+
+// address, name and optional range of a symbolic variable
+type VarSpec = (usize, String, Option<(i64, i64)>);
+
+// symbolic <program> --var addr=name[:start..end] ... [--addr n] [--target value]
+fn symbolic_main(args: &[String]) {
+    let mut program = String::from("input.txt");
+    let mut specs: Vec<VarSpec> = Vec::new();
+    let mut addr: usize = 0;
+    let mut target: Option<i64> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--var" => {
+                let spec = args.next().expect("--var needs addr=name[:start..end]");
+                let (var_addr, rest) = spec.split_once('=').expect("--var needs addr=name[:start..end]");
+                let (name, range) = match rest.split_once(':') {
+                    Some((name, range)) => {
+                        let (start, end) = range.split_once("..").expect("Variable range should be start..end");
+                        (name, Some((start.parse().expect("Invalid range start"), end.parse().expect("Invalid range end"))))
+                    },
+                    None => (rest, None),
+                };
+                specs.push((var_addr.parse().expect("Invalid variable address"), name.to_string(), range));
+            },
+            "--addr" => addr = args.next().expect("--addr needs a value").parse().expect("Invalid address"),
+            "--target" => target = Some(args.next().expect("--target needs a value").parse().expect("Invalid target")),
+            _ => program = arg.clone(),
+        }
+    }
+
+    let memory = loader::load_file(&program).unwrap_or_else(|e| panic!("{}: {}", program, e));
+    // without a range, a variable can be any address in the program like day2's noun and verb
+    let vars: Vec<(usize, VarRange)> = specs.into_iter()
+        .map(|(var_addr, name, range)| {
+            let (start, end) = range.unwrap_or((0, memory.len() as i64 - 1));
+            (var_addr, VarRange { name, start, end })
+        })
+        .collect();
+    let mut comp = SymbolicComputer::new(&memory);
+    for (var_addr, var) in vars.iter() {
+        comp.set_symbol(*var_addr, &var.name);
+    }
+    if let Err(e) = comp.run() {
+        println!("Unable to evaluate symbolically: {}", e);
+        return;
+    }
+    let expr = match comp.read(addr) {
+        Some(expr) => expr,
+        None => {
+            println!("memory[{}] depends on a symbolic address and is unknown", addr);
+            return;
+        },
+    };
+    println!("memory[{}] = {}", addr, expr);
+    for (i, out) in comp.output.iter().enumerate() {
+        match out {
+            Some(out) => println!("output[{}] = {}", i, out),
+            None => println!("output[{}] is unknown", i),
+        }
+    }
+    if let Some(target) = target {
+        let ranges: Vec<VarRange> = vars.into_iter().map(|(_, v)| v).collect();
+        match symbolic::solve(&expr, target, &ranges) {
+            Ok(solutions) if solutions.is_empty() => println!("No solutions for {}", target),
+            Ok(solutions) => {
+                for solution in solutions.iter() {
+                    let parts: Vec<String> = solution.iter().map(|(n, v)| format!("{} = {}", n, v)).collect();
+                    println!("{}", parts.join("; "));
+                }
+            },
+            Err(e) => println!("Unable to solve: {}", e),
+        }
+    }
+}
+
+// optimize <program> [--out path] [--disasm]
+fn optimize_main(args: &[String]) {
+    let mut program = String::from("input.txt");
+    let mut out: Option<String> = None;
+    let mut show_disasm = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(args.next().expect("--out needs a path").clone()),
+            "--disasm" => show_disasm = true,
+            _ => program = arg.clone(),
+        }
+    }
+
+    let memory = loader::load_file(&program).unwrap_or_else(|e| panic!("{}: {}", program, e));
+    let (image, report) = optimizer::optimize(&memory);
+    eprintln!("{:?}", report);
+    eprintln!("Image size {} -> {}", memory.len(), image.len());
+    if show_disasm {
+        let mut addr = 0;
+        while addr < image.len() {
+            match disasm::decode(&image, addr) {
+                Some(inst) => {
+                    eprintln!("{}", inst);
+                    addr += inst.len();
+                },
+                None => {
+                    eprintln!("{:>6}: {}", addr, image[addr]);
+                    addr += 1;
+                },
+            }
+        }
+    }
+    let text: Vec<String> = image.iter().map(|v| v.to_string()).collect();
+    match out {
+        Some(path) => fs::write(&path, text.join(",")).expect("Unable to write optimized program"),
+        None => println!("{}", text.join(",")),
+    }
+}
+
+// compile <program> [--out path] [--with-main]
+fn compile_main(args: &[String]) {
+    let mut program = String::from("input.txt");
+    let mut out: Option<String> = None;
+    let mut with_main = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(args.next().expect("--out needs a path").clone()),
+            "--with-main" => with_main = true,
+            _ => program = arg.clone(),
+        }
+    }
+
+    let memory = loader::load_file(&program).unwrap_or_else(|e| panic!("{}: {}", program, e));
+    let source = codegen::compile_to_rust(&memory, with_main);
+    match out {
+        Some(path) => fs::write(&path, source).expect("Unable to write generated source"),
+        None => print!("{}", source),
+    }
+}
+
+// build <source> [--out path]
+fn build_main(args: &[String]) {
+    let mut source = String::from("main.ic");
+    let mut out: Option<String> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(args.next().expect("--out needs a path").clone()),
+            _ => source = arg.clone(),
+        }
+    }
+
+    let text = fs::read_to_string(&source).unwrap_or_else(|e| panic!("{}: {}", source, e));
+    let program = lang::compile(&text).unwrap_or_else(|e| panic!("{}: {}", source, e));
+    let text: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    match out {
+        Some(path) => fs::write(&path, text.join(",")).expect("Unable to write program"),
+        None => println!("{}", text.join(",")),
+    }
+}
+
+// parses a comma separated list of values, like --input 1,2,3
+fn parse_values(spec: &str) -> Vec<i64> {
+    spec.split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().unwrap_or_else(|_| panic!("Invalid input value '{}'", v)))
+        .collect()
+}
+
+// run <program> [--input 1,2,3] [--stdin] [--ascii] [--trace] [--max-steps n] [--format list|json|ascii]
+// With --ascii, input is text, a line per --input and stdin as is, and output defaults to ascii.
+fn run_main(args: &[String]) {
+    let mut program = String::from("input.txt");
+    let mut inputs: Vec<String> = Vec::new();
+    let mut stdin = false;
+    let mut ascii = false;
+    let mut trace = false;
+    let mut max_steps: Option<u64> = None;
+    let mut format: Option<String> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => inputs.push(args.next().expect("--input needs a value").clone()),
+            "--stdin" => stdin = true,
+            "--ascii" => ascii = true,
+            "--trace" => trace = true,
+            "--max-steps" => max_steps = Some(args.next().and_then(|n| n.parse().ok()).expect("--max-steps needs a number")),
+            "--format" => format = Some(args.next().expect("--format needs list, json or ascii").clone()),
+            _ => program = arg.clone(),
+        }
+    }
+    if stdin {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).expect("Unable to read stdin");
+        inputs.push(text);
+    }
+
+    let mut comp = Computer::new(&program);
+    comp.trace = trace;
+    comp.max_steps = max_steps;
+    for (i, input) in inputs.iter().enumerate() {
+        if !ascii {
+            for value in parse_values(&input.replace(char::is_whitespace, ",")) {
+                comp.input.add(value).unwrap();
+            }
+            continue;
+        }
+        for c in input.chars() {
+            comp.input.add(c as i64).unwrap();
+        }
+        // stdin comes last and keeps its own line endings
+        if !(stdin && i == inputs.len() - 1) {
+            comp.input.add('\n' as i64).unwrap();
+        }
+    }
+
+    let halted = comp.run();
+    let state = if comp.fault.is_some() {
+        "fault"
+    } else if halted {
+        "halted"
+    } else if comp.out_of_steps() {
+        "step limit"
+    } else {
+        "waiting for input"
+    };
+    let mut outputs = Vec::new();
+    while comp.output.size() > 0 {
+        outputs.push(comp.output.remove().unwrap());
+    }
+
+    let format = format.unwrap_or_else(|| String::from(if ascii { "ascii" } else { "list" }));
+    match format.as_str() {
+        "list" => {
+            for value in outputs.iter() {
+                println!("{}", value);
+            }
+        },
+        "json" => {
+            let values: Vec<String> = outputs.iter().map(|v| v.to_string()).collect();
+            println!("{{\"state\": \"{}\", \"steps\": {}, \"output\": [{}]}}", state, comp.steps, values.join(", "));
+        },
+        "ascii" => {
+            // anything outside ascii is usually the answer, so it gets a line of its own
+            let mut text = String::new();
+            for value in outputs.iter() {
+                match *value {
+                    0..=127 => text.push(*value as u8 as char),
+                    _ => text.push_str(&format!("\n{}\n", value)),
+                }
+            }
+            print!("{}", text);
+        },
+        _ => panic!("Unknown output format {}, expected list, json or ascii", format),
+    }
+    if let Some(fault) = comp.fault.as_ref() {
+        eprintln!("Stopped after {} steps: {}", comp.steps, fault);
+    } else if !halted {
+        eprintln!("Stopped after {} steps: {}", comp.steps, state);
+    }
+}
+
 fn main() {
-    let mut comp = Computer::new("input.txt");
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|a| a.as_str()) {
+        Some("run") => return run_main(&args[1..]),
+        Some("build") => return build_main(&args[1..]),
+        Some("compile") => return compile_main(&args[1..]),
+        Some("optimize") => return optimize_main(&args[1..]),
+        Some("search") => return search_main(&args[1..]),
+        Some("symbolic") => return symbolic_main(&args[1..]),
+        _ => (),
+    }
+
+    let mut program = String::from("input.txt");
+    let mut patches: Vec<Patch> = Vec::new();
+    let mut profile = false;
+    let mut calls = false;
+    let mut ext = false;
+    let mut time = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => profile = true,
+            "--calls" => calls = true,
+            "--ext" => ext = true,
+            "--time" => time = true,
+            "--patch" => {
+                let spec = args.next().expect("--patch needs a list of address=value pairs");
+                patches.extend(patch::parse_patches(&spec).unwrap_or_else(|e| panic!("{}", e)));
+            },
+            _ => program = arg,
+        }
+    }
+
+    let mut comp = Computer::new(&program);
+    comp.apply_patches(&patches);
+    if ext {
+        extensions::register_defaults(&mut comp.extensions);
+    }
+    if profile {
+        comp.enable_profiler();
+    }
+    if calls {
+        comp.enable_call_stack();
+    }
     comp.input.add(2).unwrap();
+    let start = Instant::now();
     comp.run();
+    if time {
+        eprintln!("Ran in {:?}", start.elapsed());
+    }
+    if let Some(fault) = comp.fault.as_ref() {
+        eprintln!("Stopped: {}", fault);
+    }
+    if comp.output.size() == 0 {
+        // programs like day2's report their answer in the first memory cell
+        println!("memory[0] = {}", comp.read(0));
+    }
     while comp.output.size() > 0 {
         println!("{}", comp.output.remove().unwrap());
     }
+    if let Some(call_stack) = comp.call_stack.as_ref() {
+        println!("Functions:");
+        print!("{}", call_stack.summary());
+        println!("Backtrace:");
+        print!("{}", comp.backtrace().unwrap());
+    }
+    if let Some(profiler) = comp.profiler {
+        print!("{}", profiler.report());
+        fs::write("profile.folded", profiler.collapsed_stacks()).expect("Unable to write profile.folded");
+        println!("Collapsed stacks written to profile.folded");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+use crate::disasm;
+use crate::disasm::StaticInst;
+use crate::Opcode;
+use crate::ParamMode;
+
+// passes are repeated until nothing changes, or this many times
+const MAX_PASSES: usize = 16;
+
+#[derive(Debug, Default)]
+pub struct OptimizeReport {
+    pub passes: usize,
+    // position reads of cells nothing ever writes, turned into immediates
+    pub propagated: usize,
+    // ADD/MUL/LT/EQ with constant operands, turned into a constant move
+    pub folded: usize,
+    // JIT/JIF whose condition became known
+    pub jumps: usize,
+    // unreachable cells cleared, trailing ones are also truncated from the image
+    pub removed: usize,
+    // jumps through memory or self modifying code hide control flow,
+    // so unreachable code can't be removed and nothing is propagated or folded
+    pub unknown_flow: bool,
+}
+
+struct Analysis {
+    reachable: BTreeMap<usize, StaticInst>,
+    jump_targets: HashSet<usize>,
+    unknown_flow: bool,
+    // cells the reachable instructions write to or read from in position mode
+    written: HashSet<usize>,
+    read: HashSet<usize>,
+}
+
+// Assumes relative mode only ever addresses memory past the end of the image (the stack),
+// that the program doesn't read its own instructions through relative or computed addresses,
+// and that computed jumps only land on addresses the program stores as a constant,
+// which holds for return addresses pushed with something like ADD ret 0 [rb+n].
+fn analyse(image: &[i64]) -> Analysis {
+    let mut reachable: BTreeMap<usize, StaticInst> = BTreeMap::new();
+    let mut jump_targets = HashSet::new();
+    let mut unknown_flow = false;
+    let mut computed_jumps = false;
+    let mut todo = vec![0];
+    loop {
+        while let Some(addr) = todo.pop() {
+            if reachable.contains_key(&addr) {
+                continue;
+            }
+            let inst = match disasm::decode(image, addr) {
+                Some(inst) => inst,
+                None => {
+                    // either data or an instruction the program builds at runtime
+                    unknown_flow = true;
+                    continue;
+                },
+            };
+            let mut falls_through = true;
+            match inst.opcode {
+                Opcode::HALT => falls_through = false,
+                Opcode::JIT | Opcode::JIF => {
+                    let cond = match inst.modes[0] {
+                        ParamMode::IMMEDIATE => Some(inst.params[0] != 0),
+                        _ => None,
+                    };
+                    let taken = cond.map(|c| c == (inst.opcode == Opcode::JIT));
+                    if taken != Some(false) {
+                        if inst.modes[1] == ParamMode::IMMEDIATE && inst.params[1] >= 0 {
+                            jump_targets.insert(inst.params[1] as usize);
+                            todo.push(inst.params[1] as usize);
+                        } else {
+                            computed_jumps = true;
+                        }
+                    }
+                    falls_through = taken != Some(true);
+                },
+                _ => (),
+            }
+            if falls_through {
+                todo.push(addr + inst.len());
+            }
+            reachable.insert(addr, inst);
+        }
+        if !computed_jumps {
+            break;
+        }
+        // a constant the program stores that decodes as an instruction, without overlapping
+        // one we already know about, could be a return address a computed jump lands on
+        let covered: HashSet<usize> = reachable.values().flat_map(|i| i.addr..i.addr + i.len()).collect();
+        for inst in reachable.values() {
+            let addr = match constant_result(inst) {
+                Some(value) if inst.opcode == Opcode::ADD || inst.opcode == Opcode::MUL => value,
+                _ => continue,
+            };
+            if addr < 0 || covered.contains(&(addr as usize)) {
+                continue;
+            }
+            if let Some(target) = disasm::decode(image, addr as usize) {
+                if !(target.addr..target.addr + target.len()).any(|c| covered.contains(&c)) {
+                    jump_targets.insert(target.addr);
+                    todo.push(target.addr);
+                }
+            }
+        }
+        if todo.is_empty() {
+            break;
+        }
+    }
+
+    let mut written = HashSet::new();
+    let mut read = HashSet::new();
+    for inst in reachable.values() {
+        for (i, (mode, value)) in inst.modes.iter().zip(inst.params.iter()).enumerate() {
+            if *mode == ParamMode::POSITION && *value >= 0 {
+                if inst.write_param() == Some(i) {
+                    written.insert(*value as usize);
+                } else {
+                    read.insert(*value as usize);
+                }
+            }
+        }
+    }
+    // self modifying code means the instruction we decoded may not be the one that runs
+    reachable.retain(|addr, inst| {
+        let rewritten = (*addr..*addr + inst.len()).any(|c| written.contains(&c));
+        unknown_flow |= rewritten;
+        !rewritten
+    });
+
+    Analysis { reachable, jump_targets, unknown_flow: unknown_flow || computed_jumps, written, read }
+}
+
+// the instructions static analysis can find, excluding any the program rewrites
+pub fn reachable(image: &[i64]) -> BTreeMap<usize, StaticInst> {
+    analyse(image).reachable
+}
+
+// the value an instruction writes when every input is an immediate
+fn constant_result(inst: &StaticInst) -> Option<i64> {
+    match inst.opcode {
+        Opcode::ADD | Opcode::MUL | Opcode::LT | Opcode::EQ => (),
+        _ => return None,
+    }
+    if inst.modes[0] != ParamMode::IMMEDIATE || inst.modes[1] != ParamMode::IMMEDIATE {
+        return None;
+    }
+    let (lhs, rhs) = (inst.params[0], inst.params[1]);
+    match inst.opcode {
+        Opcode::ADD => lhs.checked_add(rhs),
+        Opcode::MUL => lhs.checked_mul(rhs),
+        Opcode::LT => Some((lhs < rhs) as i64),
+        Opcode::EQ => Some((lhs == rhs) as i64),
+        _ => None,
+    }
+}
+
+fn optimize_pass(image: &mut Vec<i64>, report: &mut OptimizeReport) -> bool {
+    let analysis = analyse(image);
+    report.unknown_flow = analysis.unknown_flow;
+    let mut changed = false;
+
+    for inst in analysis.reachable.values() {
+        // leave alone anything that the program reads as data
+        let cells = inst.addr..inst.addr + inst.len();
+        if cells.clone().any(|c| analysis.read.contains(&c)) {
+            continue;
+        }
+        let mut new = inst.clone();
+
+        // code reached through a computed jump can write anywhere, so with any of it
+        // a cell no known instruction writes isn't necessarily a constant
+        if !analysis.unknown_flow {
+            for i in 0..new.params.len() {
+                if new.write_param() == Some(i) || new.modes[i] != ParamMode::POSITION {
+                    continue;
+                }
+                let addr = new.params[i];
+                if addr >= 0 && (addr as usize) < image.len() && !analysis.written.contains(&(addr as usize)) {
+                    new.modes[i] = ParamMode::IMMEDIATE;
+                    new.params[i] = image[addr as usize];
+                    report.propagated += 1;
+                }
+            }
+        }
+
+        if let Some(value) = constant_result(&new).filter(|_| !analysis.unknown_flow) {
+            // a constant move is written as ADD value 0
+            if !(new.opcode == Opcode::ADD && new.params[1] == 0) {
+                new.opcode = Opcode::ADD;
+                new.params[0] = value;
+                new.params[1] = 0;
+                report.folded += 1;
+            }
+        }
+
+        if (new.opcode == Opcode::JIT || new.opcode == Opcode::JIF) && new.modes[0] == ParamMode::IMMEDIATE {
+            let taken = (new.params[0] != 0) == (new.opcode == Opcode::JIT);
+            if taken && !(new.opcode == Opcode::JIT && new.params[0] == 1) {
+                new.opcode = Opcode::JIT;
+                new.params[0] = 1;
+                report.jumps += 1;
+            }
+        }
+
+        if new != *inst {
+            image.splice(cells, new.encode());
+            changed = true;
+        }
+    }
+
+    // a constant written to a flag that the very next instruction tests is a known condition,
+    // as long as nothing can jump straight to the test
+    if !analysis.unknown_flow {
+        for addr in analysis.reachable.keys() {
+            // decode again, the loop above may have rewritten these
+            let inst = disasm::decode(image, *addr).unwrap();
+            let value = match constant_result(&inst) {
+                Some(value) if inst.modes[2] == ParamMode::POSITION => value,
+                _ => continue,
+            };
+            let next = match disasm::decode(image, inst.addr + inst.len()) {
+                Some(next) if analysis.reachable.contains_key(&next.addr) => next,
+                _ => continue,
+            };
+            let cells = next.addr..next.addr + next.len();
+            if (next.opcode == Opcode::JIT || next.opcode == Opcode::JIF)
+                && next.modes[0] == ParamMode::POSITION
+                && next.params[0] == inst.params[2]
+                && !analysis.jump_targets.contains(&next.addr)
+                && !cells.clone().any(|c| analysis.written.contains(&c) || analysis.read.contains(&c)) {
+                let mut new = next.clone();
+                new.modes[0] = ParamMode::IMMEDIATE;
+                new.params[0] = value;
+                image.splice(cells, new.encode());
+                report.jumps += 1;
+                changed = true;
+            }
+        }
+    }
+
+    // with every jump target known, cells no instruction covers or references are dead
+    if !analysis.unknown_flow {
+        let mut live = vec![false; image.len()];
+        for inst in analysis.reachable.values() {
+            for cell in live[inst.addr..inst.addr + inst.len()].iter_mut() {
+                *cell = true;
+            }
+        }
+        for c in analysis.written.iter().chain(analysis.read.iter()) {
+            if *c < live.len() {
+                live[*c] = true;
+            }
+        }
+        for (c, is_live) in live.iter().enumerate() {
+            if !is_live && image[c] != 0 {
+                image[c] = 0;
+                report.removed += 1;
+                changed = true;
+            }
+        }
+        // memory past the end of the image reads as zero anyway
+        while image.len() > 1 && *image.last().unwrap() == 0 && !live[image.len() - 1] {
+            image.pop();
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+pub fn optimize(program: &[i64]) -> (Vec<i64>, OptimizeReport) {
+    let mut image = program.to_vec();
+    let mut report = OptimizeReport::default();
+    while report.passes < MAX_PASSES {
+        report.passes += 1;
+        if !optimize_pass(&mut image, &mut report) {
+            break;
+        }
+    }
+    (image, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use queues::*;
+
+    use super::*;
+    use crate::loader;
+    use crate::Computer;
+
+    fn outputs(program: &[i64], inputs: &[i64]) -> Vec<i64> {
+        let mut comp = Computer::from_memory(program.to_vec());
+        for i in inputs.iter() {
+            comp.input.add(*i).unwrap();
+        }
+        assert!(comp.run());
+        assert_eq!(comp.fault, None);
+        let mut values = Vec::new();
+        while comp.output.size() > 0 {
+            values.push(comp.output.remove().unwrap());
+        }
+        values
+    }
+
+    #[test]
+    fn computed_jump_writes_are_kept() {
+        // the JIT at 0 jumps through memory to 10, which writes the 7 that 3 outputs
+        let program = [5, 22, 20, 4, 21, 99, 0, 0, 0, 0, 1101, 7, 0, 21, 1105, 1, 3, 0, 0, 0, 10, 5, 1];
+        let (image, report) = optimize(&program);
+        assert!(report.unknown_flow);
+        assert_eq!(report.propagated, 0);
+        assert_eq!(report.folded, 0);
+        assert_eq!(outputs(&program, &[]), vec![7]);
+        assert_eq!(outputs(&image, &[]), vec![7]);
+    }
+
+    #[test]
+    fn constants_are_propagated_and_folded() {
+        // output memory[9] + memory[10]
+        let program = [1, 9, 10, 11, 4, 11, 99, 0, 0, 3, 4, 0];
+        let (image, report) = optimize(&program);
+        assert!(!report.unknown_flow);
+        assert!(report.propagated > 0);
+        assert!(report.folded > 0);
+        assert_eq!(outputs(&image, &[]), vec![7]);
+    }
+
+    #[test]
+    fn boost_gives_the_same_answers() {
+        let program = loader::load_file("input.txt").unwrap();
+        let (image, _) = optimize(&program);
+        for input in [1, 2].iter() {
+            assert_eq!(outputs(&image, &[*input]), outputs(&program, &[*input]));
+        }
+    }
+}
@@ -0,0 +1,21 @@
+// a single memory override applied to a program before it runs
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub addr: usize,
+    pub value: i64,
+}
+
+// parses a list like "1=12,2=2"
+pub fn parse_patches(spec: &str) -> Result<Vec<Patch>, String> {
+    let mut patches = Vec::new();
+    for item in spec.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let mut parts = item.splitn(2, '=');
+        let addr = parts.next().unwrap().trim();
+        let value = parts.next().ok_or(format!("Patch '{}' should be address=value", item))?.trim();
+        patches.push(Patch {
+            addr: addr.parse().map_err(|_| format!("Invalid patch address '{}'", addr))?,
+            value: value.parse().map_err(|_| format!("Invalid patch value '{}'", value))?,
+        });
+    }
+    Ok(patches)
+}
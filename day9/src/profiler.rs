@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::Opcode;
+use crate::ParamMode;
+
+// memory accesses are bucketed into fixed size regions for the report
+pub const REGION_SIZE: usize = 256;
+
+// how many of the hottest addresses to include in the report
+const HOT_ADDR_COUNT: usize = 20;
+
+pub struct Profiler {
+    image_len: usize,
+    steps: u64,
+    addr_counts: HashMap<usize, (Opcode, u64)>,
+    op_counts: HashMap<Opcode, u64>,
+    mode_counts: HashMap<(Opcode, Vec<ParamMode>), u64>,
+    reads: BTreeMap<usize, u64>,
+    writes: BTreeMap<usize, u64>,
+    // pseudo call frames, identified by the address of the ARB that opened them
+    // or by the function entry when following a reconstructed call stack
+    frames: Vec<usize>,
+    frame_prefix: &'static str,
+    stack_ids: HashMap<Vec<usize>, usize>,
+    stack_counts: Vec<u64>,
+    cur_stack: usize,
+}
+
+impl Profiler {
+    pub fn new(image_len: usize) -> Self {
+        let mut stack_ids = HashMap::new();
+        stack_ids.insert(Vec::new(), 0);
+        Profiler {
+            image_len,
+            steps: 0,
+            addr_counts: HashMap::new(),
+            op_counts: HashMap::new(),
+            mode_counts: HashMap::new(),
+            reads: BTreeMap::new(),
+            writes: BTreeMap::new(),
+            frames: Vec::new(),
+            frame_prefix: "frame@",
+            stack_ids,
+            stack_counts: vec![0],
+            cur_stack: 0,
+        }
+    }
+
+    pub fn record_inst(&mut self, addr: usize, opcode: Opcode, modes: Vec<ParamMode>) {
+        self.steps += 1;
+        self.addr_counts.entry(addr).or_insert((opcode, 0)).1 += 1;
+        *self.op_counts.entry(opcode).or_insert(0) += 1;
+        *self.mode_counts.entry((opcode, modes)).or_insert(0) += 1;
+        self.stack_counts[self.cur_stack] += 1;
+    }
+
+    pub fn record_read(&mut self, addr: usize) {
+        *self.reads.entry(addr / REGION_SIZE).or_insert(0) += 1;
+    }
+
+    pub fn record_write(&mut self, addr: usize) {
+        *self.writes.entry(addr / REGION_SIZE).or_insert(0) += 1;
+    }
+
+    // a positive ARB adjustment is treated as entering a new frame
+    pub fn push_frame(&mut self, addr: usize) {
+        self.frames.push(addr);
+        self.update_stack();
+    }
+
+    // a negative ARB adjustment is treated as leaving the current frame
+    pub fn pop_frame(&mut self) {
+        if self.frames.pop().is_some() {
+            self.update_stack();
+        }
+    }
+
+    // replace the ARB pseudo frames with the function entries of a reconstructed call stack
+    pub fn set_call_frames(&mut self, entries: Vec<usize>) {
+        self.frame_prefix = "fn@";
+        self.frames = entries;
+        self.update_stack();
+    }
+
+    fn update_stack(&mut self) {
+        let next_id = self.stack_counts.len();
+        let id = *self.stack_ids.entry(self.frames.clone()).or_insert(next_id);
+        if id == next_id {
+            self.stack_counts.push(0);
+        }
+        self.cur_stack = id;
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        if self.steps == 0 {
+            return 0.0;
+        }
+        count as f64 * 100.0 / self.steps as f64
+    }
+
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "Instructions executed: {}", self.steps).unwrap();
+
+        // hottest addresses first, ties broken by address
+        let mut addrs: Vec<(&usize, &(Opcode, u64))> = self.addr_counts.iter().collect();
+        addrs.sort_by(|a, b| (b.1).1.cmp(&(a.1).1).then(a.0.cmp(b.0)));
+        writeln!(out, "\nHot addresses:").unwrap();
+        writeln!(out, "{:>8} {:>6} {:>12} {:>7}", "addr", "op", "count", "%").unwrap();
+        for (addr, (opcode, count)) in addrs.iter().take(HOT_ADDR_COUNT) {
+            writeln!(out, "{:>8} {:>6} {:>12} {:>6.2}%", addr, format!("{:?}", opcode), count, self.percent(*count)).unwrap();
+        }
+
+        let mut ops: Vec<(&Opcode, &u64)> = self.op_counts.iter().collect();
+        ops.sort_by(|a, b| b.1.cmp(a.1));
+        writeln!(out, "\nOpcodes:").unwrap();
+        for (opcode, count) in ops.iter() {
+            writeln!(out, "{:>8} {:>12} {:>6.2}%", format!("{:?}", opcode), count, self.percent(**count)).unwrap();
+        }
+
+        let mut modes: Vec<(&(Opcode, Vec<ParamMode>), &u64)> = self.mode_counts.iter().collect();
+        modes.sort_by(|a, b| b.1.cmp(a.1));
+        writeln!(out, "\nParameter modes:").unwrap();
+        for ((opcode, pmodes), count) in modes.iter() {
+            let name = format!("{:?}({})", opcode, mode_string(pmodes));
+            writeln!(out, "{:>12} {:>12} {:>6.2}%", name, count, self.percent(**count)).unwrap();
+        }
+
+        writeln!(out, "\nMemory regions:").unwrap();
+        writeln!(out, "{:>15} {:>12} {:>12}", "region", "reads", "writes").unwrap();
+        let mut regions: Vec<&usize> = self.reads.keys().chain(self.writes.keys()).collect();
+        regions.sort();
+        regions.dedup();
+        for region in regions {
+            let start = region * REGION_SIZE;
+            let end = start + REGION_SIZE - 1;
+            let label = if start < self.image_len { "" } else { " (heap)" };
+            writeln!(out, "{:>7}-{:<7} {:>12} {:>12}{}", start, end,
+                self.reads.get(region).unwrap_or(&0),
+                self.writes.get(region).unwrap_or(&0),
+                label).unwrap();
+        }
+        out
+    }
+
+    // one line per distinct stack in the collapsed format used by flamegraph.pl
+    pub fn collapsed_stacks(&self) -> String {
+        let mut stacks: Vec<(String, u64)> = Vec::new();
+        for (frames, id) in self.stack_ids.iter() {
+            let count = self.stack_counts[*id];
+            if count == 0 {
+                continue;
+            }
+            let mut name = String::from("main");
+            for addr in frames.iter() {
+                write!(name, ";{}{}", self.frame_prefix, addr).unwrap();
+            }
+            stacks.push((name, count));
+        }
+        stacks.sort();
+        let mut out = String::new();
+        for (name, count) in stacks {
+            writeln!(out, "{} {}", name, count).unwrap();
+        }
+        out
+    }
+}
+
+fn mode_string(modes: &[ParamMode]) -> String {
+    modes.iter()
+        .map(|m| match m {
+            ParamMode::POSITION => 'P',
+            ParamMode::IMMEDIATE => 'I',
+            ParamMode::RELATIVE => 'R',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use queues::*;
+
+    use crate::Computer;
+
+    #[test]
+    fn waiting_on_input_is_not_counted() {
+        let mut comp = Computer::from_memory(vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+        comp.enable_profiler();
+        assert!(!comp.run());
+        comp.input.add(1).unwrap();
+        assert!(!comp.run());
+        comp.input.add(2).unwrap();
+        assert!(comp.run());
+        let profiler = comp.profiler.unwrap();
+        assert_eq!(profiler.addr_counts[&0].1, 1);
+        assert_eq!(profiler.addr_counts[&4].1, 1);
+        assert_eq!(profiler.steps, 5);
+        assert_eq!(comp.steps, 5);
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use queues::*;
+
+use crate::patch::Patch;
+use crate::Computer;
+
+// candidates are handed out to workers in chunks of this size
+const CHUNK_SIZE: u64 = 64;
+
+// a candidate still running after this many instructions is assumed to never halt
+pub const DEFAULT_MAX_STEPS: u64 = 1_000_000;
+// each worker has its own computer, so keep their memory small
+pub const DEFAULT_MAX_MEMORY: usize = 1 << 20;
+
+// an address to patch and the inclusive range of values to try there
+#[derive(Debug, Copy, Clone)]
+pub struct PatchRange {
+    pub addr: usize,
+    pub start: i64,
+    pub end: i64,
+}
+
+impl PatchRange {
+    // parses "1=0..99"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.splitn(2, '=');
+        let addr = parts.next().unwrap().trim();
+        let range = parts.next().ok_or(format!("Range '{}' should be address=start..end", spec))?;
+        let mut bounds = range.splitn(2, "..");
+        let start = bounds.next().unwrap().trim();
+        let end = bounds.next().ok_or(format!("Range '{}' should be address=start..end", spec))?.trim();
+        let range = PatchRange {
+            addr: addr.parse().map_err(|_| format!("Invalid range address '{}'", addr))?,
+            start: start.parse().map_err(|_| format!("Invalid range start '{}'", start))?,
+            end: end.parse().map_err(|_| format!("Invalid range end '{}'", end))?,
+        };
+        if range.end < range.start {
+            return Err(format!("Range '{}' is empty", spec));
+        }
+        Ok(range)
+    }
+
+    fn len(&self) -> u64 {
+        (self.end - self.start + 1) as u64
+    }
+}
+
+pub enum Goal {
+    // the value at an address once the program halts
+    Memory(usize, i64),
+    // the last value the program output
+    Output(i64),
+}
+
+impl Goal {
+    // parses "mem0=19690720" or "out=42"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.splitn(2, '=');
+        let target = parts.next().unwrap().trim();
+        let value = parts.next().ok_or(format!("Goal '{}' should be memN=value or out=value", spec))?.trim();
+        let value = value.parse().map_err(|_| format!("Invalid goal value '{}'", value))?;
+        if target == "out" {
+            return Ok(Goal::Output(value));
+        }
+        if let Some(addr) = target.strip_prefix("mem") {
+            let addr = addr.parse().map_err(|_| format!("Invalid goal address '{}'", addr))?;
+            return Ok(Goal::Memory(addr, value));
+        }
+        Err(format!("Unknown goal '{}'", target))
+    }
+
+    fn is_met(&self, comp: &mut Computer) -> bool {
+        match self {
+            Goal::Memory(addr, value) => comp.read(*addr) == *value,
+            Goal::Output(value) => {
+                let mut last = None;
+                while comp.output.size() > 0 {
+                    last = comp.output.remove().ok();
+                }
+                last == Some(*value)
+            },
+        }
+    }
+}
+
+pub struct Search<'a> {
+    pub program: &'a [i64],
+    pub ranges: &'a [PatchRange],
+    pub inputs: &'a [i64],
+    pub goal: Goal,
+    pub threads: usize,
+    pub max_steps: u64,
+    pub max_memory: usize,
+}
+
+impl<'a> Search<'a> {
+    pub fn space_size(&self) -> u64 {
+        self.ranges.iter().map(|r| r.len()).product()
+    }
+
+    // the nth candidate, with the last range varying fastest
+    fn candidate(&self, mut n: u64) -> Vec<Patch> {
+        let mut patches = vec![Patch { addr: 0, value: 0 }; self.ranges.len()];
+        for (i, range) in self.ranges.iter().enumerate().rev() {
+            patches[i] = Patch {
+                addr: range.addr,
+                value: range.start + (n % range.len()) as i64,
+            };
+            n /= range.len();
+        }
+        patches
+    }
+
+    fn try_candidate(&self, patches: &[Patch]) -> bool {
+        // a candidate that faults or runs out of steps simply doesn't match
+        let mut comp = Computer::from_memory(self.program.to_vec());
+        comp.max_steps = Some(self.max_steps);
+        comp.max_memory = self.max_memory;
+        comp.apply_patches(patches);
+        for i in self.inputs.iter() {
+            comp.input.add(*i).unwrap();
+        }
+        comp.run();
+        if comp.out_of_steps() || comp.fault.is_some() {
+            return false;
+        }
+        self.goal.is_met(&mut comp)
+    }
+
+    // returns the first matching candidate in search order, calling progress with
+    // the number of candidates tried so far and the size of the space
+    pub fn run(&self, progress: impl Fn(u64, u64)) -> Option<Vec<Patch>> {
+        let total = self.space_size();
+        let next = AtomicU64::new(0);
+        let tried = AtomicU64::new(0);
+        let found = AtomicU64::new(u64::MAX);
+        let threads = self.threads.max(1);
+        let running = AtomicUsize::new(threads);
+
+        thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| {
+                    loop {
+                        let start = next.fetch_add(CHUNK_SIZE, Ordering::SeqCst);
+                        // stop once we are past the end or past a match found by another worker
+                        if start >= total || start > found.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        for n in start..(start + CHUNK_SIZE).min(total) {
+                            if self.try_candidate(&self.candidate(n)) {
+                                found.fetch_min(n, Ordering::SeqCst);
+                                break;
+                            }
+                            tried.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            while running.load(Ordering::SeqCst) > 0 {
+                progress(tried.load(Ordering::Relaxed), total);
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+        progress(tried.load(Ordering::Relaxed), total);
+
+        match found.load(Ordering::SeqCst) {
+            u64::MAX => None,
+            n => Some(self.candidate(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(program: &[i64], ranges: &[PatchRange], goal: Goal) -> Option<Vec<Patch>> {
+        let search = Search {
+            program,
+            ranges,
+            inputs: &[],
+            goal,
+            threads: 2,
+            max_steps: 1000,
+            max_memory: 1000,
+        };
+        search.run(|_, _| ())
+    }
+
+    fn range(addr: usize, start: i64, end: i64) -> Vec<PatchRange> {
+        vec![PatchRange { addr, start, end }]
+    }
+
+    #[test]
+    fn finds_first_match() {
+        // memory[0] = memory[1] * 3
+        let found = search(&[1102, 0, 3, 0, 99], &range(1, 0, 20), Goal::Memory(0, 30)).unwrap();
+        assert_eq!(found[0].value, 10);
+    }
+
+    #[test]
+    fn never_halting_candidates_are_rejected() {
+        assert!(search(&[1105, 1, 0], &range(1, 0, 1), Goal::Output(1)).is_none());
+    }
+
+    #[test]
+    fn bad_addresses_are_rejected() {
+        // ADD with the write target patched, far past the memory limit or negative
+        assert!(search(&[1101, 1, 1, 0, 99], &range(3, 99999999999990, 99999999999999), Goal::Memory(0, 1101)).is_none());
+        assert!(search(&[1101, 1, 1, 0, 99], &range(3, -10, -1), Goal::Memory(0, 1101)).is_none());
+        // the same for reads
+        assert!(search(&[1, 0, 0, 0, 99], &range(1, -10, -1), Goal::Memory(0, 2)).is_none());
+    }
+
+    #[test]
+    fn immediate_writes_are_rejected() {
+        // the only candidate that writes 2 does it in immediate mode
+        assert!(search(&[1101, 1, 1, 0, 99], &range(0, 11101, 11101), Goal::Memory(0, 2)).is_none());
+    }
+
+    #[test]
+    fn faults_stop_the_computer() {
+        let mut comp = Computer::from_memory(vec![1101, 1, 1, 99999999999, 99]);
+        comp.max_memory = 1000;
+        assert!(comp.run());
+        assert!(comp.fault.as_ref().unwrap().contains("memory limit"));
+        let mut comp = Computer::from_memory(vec![109, -1, 99]);
+        assert!(comp.run());
+        assert!(comp.fault.is_some());
+        let mut comp = Computer::from_memory(vec![1102, i64::MAX, 2, 0, 99]);
+        assert!(comp.run());
+        assert!(comp.fault.as_ref().unwrap().contains("overflow"));
+        assert_eq!(comp.memory[0], 1102);
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::extensions::OpcodeRegistry;
+use crate::Opcode;
+use crate::ParamMode;
+
+// give up on programs that don't halt in a reasonable time
+const MAX_STEPS: u64 = 10_000_000;
+
+// a polynomial over named integer variables, keyed by the sorted variable names of each term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Poly {
+    terms: BTreeMap<Vec<String>, i64>,
+}
+
+impl Poly {
+    pub fn constant(c: i64) -> Self {
+        let mut terms = BTreeMap::new();
+        if c != 0 {
+            terms.insert(Vec::new(), c);
+        }
+        Poly { terms }
+    }
+
+    pub fn var(name: &str) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(vec![name.to_string()], 1);
+        Poly { terms }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.len() {
+            0 => Some(0),
+            1 => self.terms.get(&Vec::new()).copied(),
+            _ => None,
+        }
+    }
+
+    pub fn add(&self, other: &Poly) -> Poly {
+        let mut terms = self.terms.clone();
+        for (vars, coeff) in other.terms.iter() {
+            *terms.entry(vars.clone()).or_insert(0) += coeff;
+        }
+        terms.retain(|_, c| *c != 0);
+        Poly { terms }
+    }
+
+    pub fn mul(&self, other: &Poly) -> Poly {
+        let mut terms: BTreeMap<Vec<String>, i64> = BTreeMap::new();
+        for (lvars, lcoeff) in self.terms.iter() {
+            for (rvars, rcoeff) in other.terms.iter() {
+                let mut vars: Vec<String> = lvars.iter().chain(rvars.iter()).cloned().collect();
+                vars.sort();
+                *terms.entry(vars).or_insert(0) += lcoeff * rcoeff;
+            }
+        }
+        terms.retain(|_, c| *c != 0);
+        Poly { terms }
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.terms.keys().all(|vars| vars.len() <= 1)
+    }
+
+    pub fn coefficient(&self, var: &str) -> i64 {
+        *self.terms.get(&vec![var.to_string()]).unwrap_or(&0)
+    }
+
+    pub fn constant_term(&self) -> i64 {
+        *self.terms.get(&Vec::new()).unwrap_or(&0)
+    }
+
+    pub fn vars(&self) -> Vec<String> {
+        let mut vars: Vec<String> = self.terms.keys().flatten().cloned().collect();
+        vars.sort();
+        vars.dedup();
+        vars
+    }
+}
+
+impl fmt::Display for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // highest degree terms first, constant last
+        let mut terms: Vec<(&Vec<String>, &i64)> = self.terms.iter().collect();
+        terms.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(b.0)));
+        for (i, (vars, coeff)) in terms.iter().enumerate() {
+            let coeff = **coeff;
+            if i == 0 {
+                if coeff < 0 {
+                    write!(f, "-")?;
+                }
+            } else if coeff < 0 {
+                write!(f, " - ")?;
+            } else {
+                write!(f, " + ")?;
+            }
+            let abs = coeff.abs();
+            if vars.is_empty() {
+                write!(f, "{}", abs)?;
+            } else {
+                if abs != 1 {
+                    write!(f, "{}*", abs)?;
+                }
+                write!(f, "{}", vars.join("*"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SymbolicError {
+    // the program depends on a symbolic value somewhere we need a concrete one
+    Symbolic { ip: usize, reason: &'static str },
+    BadInstruction { ip: usize, icode: i64 },
+    OutOfInput { ip: usize },
+    TooManySteps,
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Symbolic { ip, reason } => write!(f, "symbolic {} at ip {}", reason, ip),
+            SymbolicError::BadInstruction { ip, icode } => write!(f, "invalid instruction {} at ip {}", icode, ip),
+            SymbolicError::OutOfInput { ip } => write!(f, "ran out of input at ip {}", ip),
+            SymbolicError::TooManySteps => write!(f, "program did not halt within {} steps", MAX_STEPS),
+        }
+    }
+}
+
+// cells read through a symbolic address hold an unknown value (None), which is only
+// an error if the program later needs it concretely
+pub struct SymbolicComputer {
+    memory: Vec<Option<Poly>>,
+    inst_pointer: usize,
+    relative_base: i64,
+    pub input: VecDeque<Poly>,
+    pub output: Vec<Option<Poly>>,
+}
+
+impl SymbolicComputer {
+    pub fn new(program: &[i64]) -> Self {
+        SymbolicComputer {
+            memory: program.iter().map(|v| Some(Poly::constant(*v))).collect(),
+            inst_pointer: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn set_symbol(&mut self, addr: usize, name: &str) {
+        self.write(addr, Some(Poly::var(name)));
+    }
+
+    pub fn read(&self, addr: usize) -> Option<Poly> {
+        match self.memory.get(addr) {
+            Some(cell) => cell.clone(),
+            None => Some(Poly::constant(0)),
+        }
+    }
+
+    fn write(&mut self, addr: usize, value: Option<Poly>) {
+        if self.memory.len() <= addr {
+            self.memory.resize(addr + 10, Some(Poly::constant(0)));
+        }
+        self.memory[addr] = value;
+    }
+
+    fn concrete(&self, value: &Option<Poly>, reason: &'static str) -> Result<i64, SymbolicError> {
+        value.as_ref()
+            .and_then(|v| v.as_constant())
+            .ok_or(SymbolicError::Symbolic { ip: self.inst_pointer, reason })
+    }
+
+    // None if the parameter word is itself symbolic
+    fn param_addr(&self, mode: ParamMode, raw: &Option<Poly>) -> Result<Option<usize>, SymbolicError> {
+        let raw = match raw.as_ref().and_then(|r| r.as_constant()) {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let addr = match mode {
+            ParamMode::POSITION => raw,
+            ParamMode::RELATIVE => self.relative_base + raw,
+            ParamMode::IMMEDIATE => return Err(SymbolicError::BadInstruction { ip: self.inst_pointer, icode: raw }),
+        };
+        if addr < 0 {
+            return Err(SymbolicError::BadInstruction { ip: self.inst_pointer, icode: raw });
+        }
+        Ok(Some(addr as usize))
+    }
+
+    fn param_value(&self, mode: ParamMode, raw: &Option<Poly>) -> Result<Option<Poly>, SymbolicError> {
+        match mode {
+            ParamMode::IMMEDIATE => Ok(raw.clone()),
+            _ => Ok(self.param_addr(mode, raw)?.and_then(|addr| self.read(addr))),
+        }
+    }
+
+    fn write_target(&self, mode: ParamMode, raw: &Option<Poly>) -> Result<usize, SymbolicError> {
+        self.param_addr(mode, raw)?.ok_or(SymbolicError::Symbolic { ip: self.inst_pointer, reason: "write address" })
+    }
+
+    pub fn run(&mut self) -> Result<(), SymbolicError> {
+        let registry = OpcodeRegistry::new();
+        for _ in 0..MAX_STEPS {
+            let ip = self.inst_pointer;
+            let icode = self.concrete(&self.read(ip), "instruction")?;
+            let opcode = match icode % 100 {
+                1..=9 | 99 if icode >= 0 => Opcode::from_i64(icode % 100, &registry).map_err(|_| SymbolicError::BadInstruction { ip, icode })?,
+                _ => return Err(SymbolicError::BadInstruction { ip, icode }),
+            };
+            let count = opcode.param_count(&registry) as usize;
+            let mut modes = Vec::new();
+            let mut raws = Vec::new();
+            for i in 0..count {
+                let digit = (icode / (100 * 10i64.pow(i as u32))) % 10;
+                modes.push(ParamMode::from_i64(digit).map_err(|_| SymbolicError::BadInstruction { ip, icode })?);
+                raws.push(self.read(ip + 1 + i));
+            }
+            let mut next_ip = ip + count + 1;
+            match opcode {
+                Opcode::ADD | Opcode::MUL | Opcode::LT | Opcode::EQ => {
+                    let lhs = self.param_value(modes[0], &raws[0])?;
+                    let rhs = self.param_value(modes[1], &raws[1])?;
+                    let idx = self.write_target(modes[2], &raws[2])?;
+                    let res = match opcode {
+                        Opcode::ADD => lhs.and_then(|l| rhs.map(|r| l.add(&r))),
+                        Opcode::MUL => lhs.and_then(|l| rhs.map(|r| l.mul(&r))),
+                        _ => {
+                            let lhs = self.concrete(&lhs, "comparison")?;
+                            let rhs = self.concrete(&rhs, "comparison")?;
+                            let test = if opcode == Opcode::LT { lhs < rhs } else { lhs == rhs };
+                            Some(Poly::constant(test as i64))
+                        },
+                    };
+                    self.write(idx, res);
+                },
+                Opcode::INP => {
+                    let idx = self.write_target(modes[0], &raws[0])?;
+                    let value = self.input.pop_front().ok_or(SymbolicError::OutOfInput { ip })?;
+                    self.write(idx, Some(value));
+                },
+                Opcode::OUT => {
+                    let value = self.param_value(modes[0], &raws[0])?;
+                    self.output.push(value);
+                },
+                Opcode::JIT | Opcode::JIF => {
+                    let test = self.param_value(modes[0], &raws[0])?;
+                    let test = self.concrete(&test, "jump condition")?;
+                    let target = self.param_value(modes[1], &raws[1])?;
+                    let target = self.concrete(&target, "jump target")?;
+                    if (test != 0) == (opcode == Opcode::JIT) {
+                        next_ip = target as usize;
+                    }
+                },
+                Opcode::ARB => {
+                    let offset = self.param_value(modes[0], &raws[0])?;
+                    self.relative_base += self.concrete(&offset, "relative base")?;
+                },
+                Opcode::HALT => return Ok(()),
+                Opcode::EXT(_) => unreachable!(),
+            }
+            self.inst_pointer = next_ip;
+        }
+        Err(SymbolicError::TooManySteps)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VarRange {
+    pub name: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+// finds every assignment in range with expr == target, for expressions linear in their variables.
+// all but the last variable are enumerated and the last is solved for directly.
+pub fn solve(expr: &Poly, target: i64, ranges: &[VarRange]) -> Result<Vec<Vec<(String, i64)>>, String> {
+    if !expr.is_linear() {
+        return Err(format!("{} is not linear", expr));
+    }
+    for var in expr.vars() {
+        if !ranges.iter().any(|r| r.name == var) {
+            return Err(format!("No range given for {}", var));
+        }
+    }
+    // only variables that actually appear matter, the rest are free
+    let vars: Vec<&VarRange> = ranges.iter().filter(|r| expr.coefficient(&r.name) != 0).collect();
+    let mut solutions = Vec::new();
+    if vars.is_empty() {
+        if expr.constant_term() == target {
+            solutions.push(Vec::new());
+        }
+        return Ok(solutions);
+    }
+    let (last, rest) = vars.split_last().unwrap();
+    let last_coeff = expr.coefficient(&last.name);
+    let mut assignment: Vec<i64> = rest.iter().map(|r| r.start).collect();
+    loop {
+        let partial: i64 = rest.iter().zip(assignment.iter())
+            .map(|(r, v)| expr.coefficient(&r.name) * v)
+            .sum();
+        let remainder = target - expr.constant_term() - partial;
+        if remainder % last_coeff == 0 {
+            let value = remainder / last_coeff;
+            if value >= last.start && value <= last.end {
+                let mut solution: Vec<(String, i64)> = rest.iter().zip(assignment.iter())
+                    .map(|(r, v)| (r.name.clone(), *v))
+                    .collect();
+                solution.push((last.name.clone(), value));
+                solutions.push(solution);
+            }
+        }
+        // advance the enumerated variables like an odometer
+        let mut i = rest.len();
+        loop {
+            if i == 0 {
+                return Ok(solutions);
+            }
+            i -= 1;
+            if assignment[i] < rest[i].end {
+                assignment[i] += 1;
+                break;
+            }
+            assignment[i] = rest[i].start;
+        }
+    }
+}